@@ -0,0 +1,16 @@
+// Helpers shared by the hand timed benches. This lives in a directory so
+// cargo doesn't pick it up as a bench of its own.
+
+use std::time::{Duration, Instant};
+
+// The best of a few runs, to keep noise from other processes out
+pub fn best_of<T>(runs: usize, mut f: impl FnMut() -> T) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
@@ -1,33 +1,20 @@
-// Attempt to setup stable Rust benchmarking
-
-// #[macro_use]
-// extern crate bencher;
-// extern crate aoc_2022;
-// use std::fs;
-
-// use aoc_2022::days::day6;
-// use bencher::Bencher;
-
-
-// fn bench_find_first_marker(b: &mut Bencher) {
-//     let input = fs::read_to_string(format!("./input/day6.txt")).unwrap();
-//     b.iter(|| day6::find_first_marker(&input, 14));
-// }
-
-// fn bench_find_first_marker_optimized(b: &mut Bencher) {
-//     let input = fs::read_to_string(format!("./input/day6.txt")).unwrap();
-//     b.iter(|| day6::find_first_marker_optimized(&input, 14));
-// }
-
-// // fn part_one(b: &mut Bencher){
-// //     let input = fs::read_to_string(format!("./input/day8.txt")).unwrap();
-// //     b.iter(|| day8::part1(&input));
-// // }
-
-// // fn part_two(b: &mut Bencher) {
-// //     let input = fs::read_to_string(format!("./input/day8.txt")).unwrap();
-// //     b.iter(|| day8::part2(&input));
-// // }
-
-// benchmark_group!(benches, bench_find_first_marker, bench_find_first_marker_optimized);
-// benchmark_main!(benches);
+// Stable Rust benchmarks, timed by hand rather than with the unstable test
+// crate. Run with `cargo bench --bench day6`.
+
+extern crate aoc_2022;
+mod common;
+
+use aoc_2022::days::day6;
+use common::best_of;
+
+fn main() {
+    // 13 distinct letters repeating never give a 14 byte marker, so both
+    // have to read the whole stream
+    let stream: String = "abcdefghijklm".chars().cycle().take(10_000_000).collect();
+    let optimized = best_of(5, || {
+        day6::find_first_marker_optimized(&stream, 14).is_err()
+    });
+    let streaming = best_of(5, || day6::first_markers(stream.as_bytes(), &[14]).ok());
+    println!("find_first_marker_optimized {optimized:?}");
+    println!("first_markers               {streaming:?}");
+}
@@ -0,0 +1,39 @@
+// Scenic scores on large generated forests, timed by hand like the day 6
+// bench. Run with `cargo bench --bench day8`.
+
+extern crate aoc_2022;
+mod common;
+
+use aoc_2022::days::{common::random::Lcg, day8};
+use common::best_of;
+
+// Random heights from a fixed seed
+fn random_forest(size: usize) -> Vec<Vec<u8>> {
+    let mut lcg = Lcg::new(8);
+    (0..size)
+        .map(|_| (0..size).map(|_| lcg.below(10) as u8).collect())
+        .collect()
+}
+
+// Every tree is one taller than those above and to the left of it, so the
+// walk in the reference version is quadratic while the stacks aren't
+fn slope(size: usize) -> Vec<Vec<u16>> {
+    (0..size)
+        .map(|i| (0..size).map(|j| (i + j) as u16).collect())
+        .collect()
+}
+
+fn main() {
+    let forest = random_forest(5000);
+    let stacks = best_of(3, || day8::scenic_scores(&forest));
+    let reference = best_of(3, || day8::scenic_scores_reference(&forest));
+    println!("random 5000x5000  stacks {stacks:?}  reference {reference:?}");
+
+    let forest = slope(5000);
+    let stacks = best_of(3, || day8::scenic_scores(&forest));
+    println!("slope 5000x5000   stacks {stacks:?}");
+    let forest = slope(1000);
+    let stacks = best_of(3, || day8::scenic_scores(&forest));
+    let reference = best_of(1, || day8::scenic_scores_reference(&forest));
+    println!("slope 1000x1000   stacks {stacks:?}  reference {reference:?}");
+}
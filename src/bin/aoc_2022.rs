@@ -1,9 +1,73 @@
 extern crate aoc_2022;
+use aoc_2022::days::common::trace::{self, Debugger, Trace};
+use aoc_2022::days::common::visualize::{self, Visualize};
 use aoc_2022::days::*;
 
-use std::error::Error;
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+    time::Duration,
+};
 
+// Usage:
+//   aoc_2022 [--visualize[=terminal|text|ppm|png|gif]] [--day=N] [--delay=MS]
+//   aoc_2022 --trace --day=N > dayN.trace
+//   aoc_2022 --replay=FILE [--seek=STEP]
+//   aoc_2022 --diff=FILE_A,FILE_B
+//   aoc_2022 --steps[=1|2]
+//   aoc_2022 --frames
+//   aoc_2022 --materialise=DIR
+//   aoc_2022 --log-from=DIR
+//   aoc_2022 --survey=DIR [--top=N]
+//   aoc_2022 --rope[=KNOTS]
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let day = flag(&args, "--day", "")
+        .map(|day| day.parse())
+        .transpose()?;
+    if let Some(renderer) = flag(&args, "--visualize", "terminal") {
+        let delay = flag(&args, "--delay", "50").unwrap_or("50").parse()?;
+        return run_visualizations(renderer, day, Duration::from_millis(delay));
+    }
+    if flag(&args, "--trace", "").is_some() {
+        return record_trace(day.ok_or("--trace needs a --day.")?);
+    }
+    if let Some(path) = flag(&args, "--replay", "") {
+        let seek = flag(&args, "--seek", "")
+            .map(|step| step.parse())
+            .transpose()?;
+        return replay_trace(path, seek);
+    }
+    if let Some(part) = flag(&args, "--steps", "1") {
+        return day5::print_steps(part.parse()?);
+    }
+    if flag(&args, "--frames", "").is_some() {
+        return day6::report();
+    }
+    if let Some(dir) = flag(&args, "--materialise", "") {
+        return day7::materialise_input(Path::new(dir));
+    }
+    if let Some(dir) = flag(&args, "--log-from", "") {
+        return day7::print_log(Path::new(dir));
+    }
+    if let Some(dir) = flag(&args, "--survey", "") {
+        if dir.is_empty() {
+            return Err("--survey needs a directory, as in --survey=DIR.".into());
+        }
+        let top = flag(&args, "--top", "10").unwrap_or("10").parse()?;
+        return day8::export(Path::new(dir), top);
+    }
+    if let Some(knots) = flag(&args, "--rope", "10") {
+        return day9::print_motions(knots.parse()?);
+    }
+    if let Some(paths) = flag(&args, "--diff", "") {
+        let (a, b) = paths.split_once(',').ok_or("--diff needs two files.")?;
+        return diff_traces(a, b);
+    }
+
     day1::solution()?;
     day2::solution()?;
     day3::solution()?;
@@ -19,3 +83,94 @@ fn main() -> Result<(), Box<dyn Error>> {
     day13::solution()?;
     day14::solution()
 }
+
+// Find `name` or `name=value` in the args. A bare flag gives `default`.
+fn flag<'a>(args: &'a [String], name: &str, default: &'a str) -> Option<&'a str> {
+    args.iter().find_map(|arg| match arg.strip_prefix(name)? {
+        "" => Some(default),
+        value => value.strip_prefix('='),
+    })
+}
+
+type Visualization = fn(&mut dyn Visualize) -> common::Result<()>;
+
+// Run the simulating days through a fresh renderer each. Image renderers write
+// into ./visualize/dayN.
+fn run_visualizations(
+    renderer: &str,
+    only_day: Option<u8>,
+    delay: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let days: [(u8, Visualization); 5] = [
+        (5, day5::visualize),
+        (9, day9::visualize),
+        (10, day10::visualize),
+        (12, day12::visualize),
+        (14, day14::visualize),
+    ];
+    if let Some(day) = only_day {
+        if !days.iter().any(|(number, _)| *number == day) {
+            return Err(format!("Day {day} has no visualization.").into());
+        }
+    }
+    for (day, run) in days {
+        if only_day.is_none_or(|only| only == day) {
+            let dir = Path::new("./visualize").join(format!("day{day}"));
+            let mut vis = visualize::renderer(renderer, &dir, delay)?;
+            run(vis.as_mut())?;
+            vis.finish()?;
+        }
+    }
+    Ok(())
+}
+
+fn record_trace(day: u8) -> Result<(), Box<dyn Error>> {
+    let trace = match day {
+        9 => day9::trace()?,
+        11 => day11::trace()?,
+        14 => day14::trace()?,
+        _ => return Err(format!("Day {day} has no trace.").into()),
+    };
+    trace.write(&mut io::stdout().lock())
+}
+
+fn read_trace(path: &str) -> Result<Trace, Box<dyn Error>> {
+    Trace::read(BufReader::new(File::open(path)?))
+}
+
+// Print the state after the given step, or every step in turn without one
+fn replay_trace(path: &str, seek: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let trace = read_trace(path)?;
+    let mut debugger = Debugger::new(&trace);
+    if let Some(step) = seek {
+        debugger.seek(step);
+        println!(
+            "Step {}",
+            debugger.current_step().ok_or("Nothing recorded yet.")?
+        );
+        for ((kind, id), values) in debugger.state() {
+            println!("{kind} {id}: {values:?}");
+        }
+    } else {
+        while let Some(events) = debugger.step_forward() {
+            events.iter().for_each(|event| println!("{event}"));
+        }
+    }
+    Ok(())
+}
+
+fn diff_traces(a: &str, b: &str) -> Result<(), Box<dyn Error>> {
+    let (a, b) = (read_trace(a)?, read_trace(b)?);
+    match trace::first_divergence(&a, &b) {
+        Some(step) => {
+            println!("First divergence at step {step}");
+            for (step, a_events, b_events) in trace::diff(&a, &b).iter().take(10) {
+                println!("Step {step}");
+                a_events.iter().for_each(|event| println!("  < {event}"));
+                b_events.iter().for_each(|event| println!("  > {event}"));
+            }
+        }
+        None => println!("Traces are identical"),
+    }
+    Ok(())
+}
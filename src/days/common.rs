@@ -4,6 +4,13 @@ use std::{
     io::{self, BufRead},
 };
 
+pub mod bitset;
+pub mod cycle;
+pub mod interval;
+pub mod random;
+pub mod trace;
+pub mod visualize;
+
 // Result alias with an implicit boxed error to clean up type sigs
 pub type Result<T> = core::result::Result<T, Box<dyn Error>>;
 
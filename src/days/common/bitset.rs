@@ -0,0 +1,315 @@
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+
+// Fixed width bitsets backed by a single unsigned integer. Elements are bit
+// indexes, so inserting anything at or beyond the width panics.
+macro_rules! fixed_bitset {
+    ($name:ident, $word:ty) => {
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+        pub struct $name($word);
+
+        impl $name {
+            pub const WIDTH: usize = <$word>::BITS as usize;
+
+            pub fn new() -> Self {
+                $name(0)
+            }
+
+            pub fn from_bits(bits: $word) -> Self {
+                $name(bits)
+            }
+
+            pub fn bits(&self) -> $word {
+                self.0
+            }
+
+            // Returns whether the element was newly inserted
+            pub fn insert(&mut self, index: usize) -> bool {
+                assert!(index < Self::WIDTH, "{index} is outside the bitset");
+                let missing = !self.contains(index);
+                self.0 |= 1 << index;
+                missing
+            }
+
+            // Returns whether the element was present
+            pub fn remove(&mut self, index: usize) -> bool {
+                let present = self.contains(index);
+                if present {
+                    self.0 &= !(1 << index);
+                }
+                present
+            }
+
+            // Flip the element in or out of the set
+            pub fn toggle(&mut self, index: usize) {
+                assert!(index < Self::WIDTH, "{index} is outside the bitset");
+                self.0 ^= 1 << index;
+            }
+
+            pub fn contains(&self, index: usize) -> bool {
+                index < Self::WIDTH && self.0 & (1 << index) != 0
+            }
+
+            pub fn union(&self, other: &Self) -> Self {
+                $name(self.0 | other.0)
+            }
+
+            pub fn intersection(&self, other: &Self) -> Self {
+                $name(self.0 & other.0)
+            }
+
+            pub fn difference(&self, other: &Self) -> Self {
+                $name(self.0 & !other.0)
+            }
+
+            pub fn symmetric_difference(&self, other: &Self) -> Self {
+                $name(self.0 ^ other.0)
+            }
+
+            pub fn is_subset(&self, other: &Self) -> bool {
+                self.0 & !other.0 == 0
+            }
+
+            // Number of elements, i.e. the popcount
+            pub fn len(&self) -> usize {
+                self.0.count_ones() as usize
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+
+            pub fn first(&self) -> Option<usize> {
+                self.iter().next()
+            }
+
+            // Elements in ascending order
+            pub fn iter(&self) -> impl Iterator<Item = usize> {
+                let mut bits = self.0;
+                std::iter::from_fn(move || {
+                    if bits == 0 {
+                        None
+                    } else {
+                        let index = bits.trailing_zeros() as usize;
+                        bits &= bits - 1;
+                        Some(index)
+                    }
+                })
+            }
+        }
+
+        impl FromIterator<usize> for $name {
+            fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+                let mut set = $name::new();
+                iter.into_iter().for_each(|index| {
+                    set.insert(index);
+                });
+                set
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = Self;
+            fn bitor(self, other: Self) -> Self {
+                self.union(&other)
+            }
+        }
+
+        impl BitAnd for $name {
+            type Output = Self;
+            fn bitand(self, other: Self) -> Self {
+                self.intersection(&other)
+            }
+        }
+
+        impl BitXor for $name {
+            type Output = Self;
+            fn bitxor(self, other: Self) -> Self {
+                self.symmetric_difference(&other)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+            fn sub(self, other: Self) -> Self {
+                self.difference(&other)
+            }
+        }
+    };
+}
+
+fixed_bitset!(BitSet64, u64);
+fixed_bitset!(BitSet128, u128);
+
+// Bitset over a vector of words that grows to fit whatever is inserted.
+// Trailing empty words are trimmed so equal sets compare equal.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct GrowableBitSet {
+    words: Vec<u64>,
+}
+
+impl GrowableBitSet {
+    pub fn new() -> Self {
+        GrowableBitSet::default()
+    }
+
+    pub fn with_capacity(bits: usize) -> Self {
+        GrowableBitSet {
+            words: Vec::with_capacity(bits.div_ceil(64)),
+        }
+    }
+
+    fn split(index: usize) -> (usize, u64) {
+        (index / 64, 1 << (index % 64))
+    }
+
+    fn trim(mut self) -> Self {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+        self
+    }
+
+    // Returns whether the element was newly inserted
+    pub fn insert(&mut self, index: usize) -> bool {
+        let (word, mask) = Self::split(index);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let missing = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        missing
+    }
+
+    // Returns whether the element was present
+    pub fn remove(&mut self, index: usize) -> bool {
+        let present = self.contains(index);
+        if present {
+            let (word, mask) = Self::split(index);
+            self.words[word] &= !mask;
+            *self = std::mem::take(self).trim();
+        }
+        present
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        let (word, mask) = Self::split(index);
+        self.words.get(word).is_some_and(|bits| bits & mask != 0)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let (long, short) = if self.words.len() >= other.words.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let mut words = long.words.clone();
+        words
+            .iter_mut()
+            .zip(&short.words)
+            .for_each(|(a, b)| *a |= b);
+        GrowableBitSet { words }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let words = self
+            .words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| a & b)
+            .collect();
+        GrowableBitSet { words }.trim()
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut words = self.words.clone();
+        words
+            .iter_mut()
+            .zip(&other.words)
+            .for_each(|(a, b)| *a &= !b);
+        GrowableBitSet { words }.trim()
+    }
+
+    // Number of elements, i.e. the popcount
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // Elements in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(word, bits)| {
+            BitSet64::from_bits(*bits)
+                .iter()
+                .map(move |index| word * 64 + index)
+        })
+    }
+}
+
+impl FromIterator<usize> for GrowableBitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = GrowableBitSet::new();
+        iter.into_iter().for_each(|index| {
+            set.insert(index);
+        });
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::days::common::Result;
+
+    #[test]
+    fn test_fixed_set_operations() -> Result<()> {
+        let a: BitSet64 = [1, 3, 5, 63].into_iter().collect();
+        let b: BitSet64 = [3, 4, 5].into_iter().collect();
+        assert_eq!((a | b).iter().collect::<Vec<_>>(), [1, 3, 4, 5, 63]);
+        assert_eq!((a & b).iter().collect::<Vec<_>>(), [3, 5]);
+        assert_eq!((a - b).iter().collect::<Vec<_>>(), [1, 63]);
+        assert_eq!((a ^ b).len(), 3);
+        assert!((a & b).is_subset(&a));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_insert_remove() -> Result<()> {
+        let mut set = BitSet128::new();
+        assert!(set.insert(127));
+        assert!(!set.insert(127));
+        assert!(set.contains(127));
+        assert!(!set.contains(200));
+        set.toggle(0);
+        assert_eq!(set.first(), Some(0));
+        assert!(set.remove(127));
+        assert!(!set.remove(127));
+        assert_eq!(set.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_fixed_insert_out_of_range() {
+        BitSet64::new().insert(64);
+    }
+
+    #[test]
+    fn test_growable_set_operations() -> Result<()> {
+        let a: GrowableBitSet = [1, 70, 300].into_iter().collect();
+        let b: GrowableBitSet = [70, 5].into_iter().collect();
+        assert_eq!(a.union(&b).iter().collect::<Vec<_>>(), [1, 5, 70, 300]);
+        assert_eq!(a.intersection(&b).iter().collect::<Vec<_>>(), [70]);
+        assert_eq!(a.difference(&b).len(), 2);
+        let mut c = a.clone();
+        c.remove(300);
+        assert_eq!(c, [1, 70].into_iter().collect());
+        assert!(b.difference(&b).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use std::{collections::HashMap, hash::Hash};
+
+// A sequence x0, x1 = f(x0), ... that repeats. The first `start` states are a
+// lead in, after which every state repeats every `period` steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    // The earliest step with the same state as `step`
+    pub fn reduce(&self, step: usize) -> usize {
+        if step < self.start {
+            step
+        } else {
+            self.start + (step - self.start) % self.period
+        }
+    }
+
+    // Extend an accumulator to any step. `history[n]` is its value after n
+    // steps and must reach at least one full period past the start. Every
+    // period after the start adds the same amount as the first one did.
+    pub fn extrapolate(&self, history: &[i64], step: usize) -> Option<i64> {
+        if let Some(value) = history.get(step) {
+            return Some(*value);
+        }
+        let per_period = history.get(self.start + self.period)? - history.get(self.start)?;
+        let periods = ((step - self.start) / self.period) as i64;
+        Some(history[self.reduce(step)] + periods * per_period)
+    }
+}
+
+// Floyd's tortoise and hare. Uses constant memory but steps each state up to
+// three times. None if no repeat is found within `limit` steps.
+pub fn floyd<T: PartialEq>(initial: T, step: impl Fn(&T) -> T, limit: usize) -> Option<Cycle> {
+    let mut tortoise = step(&initial);
+    let mut hare = step(&tortoise);
+    let mut steps = 1;
+    while tortoise != hare {
+        if steps > limit {
+            return None;
+        }
+        tortoise = step(&tortoise);
+        hare = step(&step(&hare));
+        steps += 1;
+    }
+
+    // The tortoise is now a multiple of the period in, walking both from the
+    // start at the same speed they meet where the cycle begins
+    let mut start = 0;
+    tortoise = initial;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    let mut period = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        period += 1;
+    }
+    Some(Cycle { start, period })
+}
+
+// Brent's algorithm. Finds the period first by searching powers of two, which
+// usually takes fewer steps than Floyd. None if no repeat is found within
+// `limit` steps.
+pub fn brent<T: PartialEq + Clone>(
+    initial: T,
+    step: impl Fn(&T) -> T,
+    limit: usize,
+) -> Option<Cycle> {
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    let mut steps = 1;
+    while tortoise != hare {
+        if steps > limit {
+            return None;
+        }
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+        steps += 1;
+    }
+
+    // Start a period apart and walk together until they meet
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+    for _ in 0..period {
+        hare = step(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+    Some(Cycle { start, period })
+}
+
+// Remember every state seen in a hash map. Uses memory for the whole lead in
+// and period but steps each state only once, and works with simulations that
+// update in place. None if no repeat is found within `limit` steps.
+pub fn find_with_states<T: Hash + Eq + Clone>(
+    initial: T,
+    mut step: impl FnMut(&T) -> T,
+    limit: usize,
+) -> Option<Cycle> {
+    let mut seen: HashMap<T, usize> = HashMap::new();
+    let mut state = initial;
+    for index in 0..=limit {
+        if let Some(first) = seen.insert(state.clone(), index) {
+            return Some(Cycle {
+                start: first,
+                period: index - first,
+            });
+        }
+        state = step(&state);
+    }
+    None
+}
+
+// Run a simulation until the key of its state repeats, then extrapolate the
+// accumulator to `target` steps. `step` advances the state in place and
+// returns how much the accumulator grew. Only the key is hashed, so it can
+// be a summary of the state such as the shape of the top of a tower.
+pub fn extrapolate<S, K: Hash + Eq>(
+    mut state: S,
+    mut step: impl FnMut(&mut S) -> i64,
+    key: impl Fn(&S) -> K,
+    target: usize,
+) -> i64 {
+    let mut seen: HashMap<K, usize> = HashMap::new();
+    let mut history = vec![0];
+    for index in 0.. {
+        if index == target {
+            return history[index];
+        }
+        if let Some(first) = seen.insert(key(&state), index) {
+            let cycle = Cycle {
+                start: first,
+                period: index - first,
+            };
+            if let Some(value) = cycle.extrapolate(&history, target) {
+                return value;
+            }
+        }
+        let gain = step(&mut state);
+        history.push(history[index] + gain);
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::days::common::Result;
+
+    // x -> x^2 + 1 mod 255 from 3 runs 3, 10, 101, 2, 5, 26, 167, 95, 101, ...
+    fn step(x: &u32) -> u32 {
+        (x * x + 1) % 255
+    }
+
+    #[test]
+    fn test_algorithms_agree() -> Result<()> {
+        let expected = Some(Cycle {
+            start: 2,
+            period: 6,
+        });
+        assert_eq!(floyd(3, step, 1000), expected);
+        assert_eq!(brent(3, step, 1000), expected);
+        assert_eq!(find_with_states(3, step, 1000), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_cycle_within_limit() -> Result<()> {
+        let count = |x: &u64| x + 1;
+        assert_eq!(floyd(0, count, 100), None);
+        assert_eq!(brent(0, count, 100), None);
+        assert_eq!(find_with_states(0, count, 100), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reduce_and_extrapolate() -> Result<()> {
+        let cycle = Cycle {
+            start: 2,
+            period: 3,
+        };
+        assert_eq!(cycle.reduce(1), 1);
+        assert_eq!(cycle.reduce(9), 3);
+        // Gains of 5, 5 then 1, 2, 3 repeating
+        let history = [0, 5, 10, 11, 13, 16];
+        assert_eq!(cycle.extrapolate(&history, 4), Some(13));
+        assert_eq!(cycle.extrapolate(&history, 8), Some(22));
+        assert_eq!(cycle.extrapolate(&history[..4], 8), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extrapolate_simulation() -> Result<()> {
+        // Add the state to the total and advance it mod 4 from 7, giving
+        // gains of 7, then 0, 1, 2, 3 repeating
+        let gain = |state: &mut u64| {
+            let gain = *state as i64;
+            *state = (*state + 1) % 4;
+            gain
+        };
+        let brute_force = |target| {
+            let mut state = 7;
+            (0..target).map(|_| gain(&mut state)).sum::<i64>()
+        };
+        for target in [0, 1, 5, 10, 1001] {
+            assert_eq!(extrapolate(7, gain, |s| *s, target), brute_force(target));
+        }
+        assert_eq!(
+            extrapolate(7, gain, |s| *s, 1_000_000_000_001),
+            7 + 1_500_000_000_000
+        );
+        Ok(())
+    }
+}
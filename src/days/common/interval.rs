@@ -0,0 +1,355 @@
+use std::{
+    fmt,
+    ops::{Add, Sub},
+};
+
+// Integer types usable as interval bounds. Inclusive intervals are stored as
+// half open ones, which needs the next value after the last one. There is none
+// after the maximum, and lengths can be too big for the type, so the arithmetic
+// is checked.
+pub trait Endpoint: Copy + Ord + Default + Add<Output = Self> + Sub<Output = Self> {
+    fn successor(self) -> Option<Self>;
+    fn checked_plus(self, other: Self) -> Option<Self>;
+    fn checked_minus(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! endpoint {
+    ($($int:ty),*) => {
+        $(impl Endpoint for $int {
+            fn successor(self) -> Option<Self> {
+                self.checked_add(1)
+            }
+
+            fn checked_plus(self, other: Self) -> Option<Self> {
+                self.checked_add(other)
+            }
+
+            fn checked_minus(self, other: Self) -> Option<Self> {
+                self.checked_sub(other)
+            }
+        })*
+    };
+}
+
+endpoint!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+// The half open interval [start, end). Empty whenever start >= end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Interval<T> {
+    start: T,
+    end: T,
+}
+
+impl<T: Endpoint> Interval<T> {
+    // [start, end)
+    pub fn new(start: T, end: T) -> Self {
+        Interval { start, end }
+    }
+
+    // [first, last], or None when last is the maximum and can't be stored
+    pub fn inclusive(first: T, last: T) -> Option<Self> {
+        Some(Interval {
+            start: first,
+            end: last.successor()?,
+        })
+    }
+
+    pub fn start(&self) -> T {
+        self.start
+    }
+
+    pub fn end(&self) -> T {
+        self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    // None when the number of values doesn't fit in T, like [-100, 100) for i8
+    pub fn len(&self) -> Option<T> {
+        if self.is_empty() {
+            Some(T::default())
+        } else {
+            self.end.checked_minus(self.start)
+        }
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.start <= value && value < self.end
+    }
+
+    // Whether every value of other is also in self. Always true for empty.
+    pub fn contains_interval(&self, other: &Self) -> bool {
+        other.is_empty() || (self.start <= other.start && other.end <= self.end)
+    }
+
+    pub fn overlaps(&self, other: &Self) -> bool {
+        !self.is_empty() && !other.is_empty() && self.start < other.end && other.start < self.end
+    }
+
+    // Overlapping or directly next to each other, so the union is one interval
+    pub fn touches(&self, other: &Self) -> bool {
+        !self.is_empty() && !other.is_empty() && self.start <= other.end && other.start <= self.end
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let intersection = Interval::new(self.start.max(other.start), self.end.min(other.end));
+        (!intersection.is_empty()).then_some(intersection)
+    }
+
+    // None when there is a gap between the two, the union isn't an interval
+    pub fn union(&self, other: &Self) -> Option<Self> {
+        if self.is_empty() {
+            Some(*other)
+        } else if other.is_empty() || self.touches(other) {
+            Some(self.hull(other))
+        } else {
+            None
+        }
+    }
+
+    // The smallest interval containing both
+    pub fn hull(&self, other: &Self) -> Self {
+        if self.is_empty() {
+            *other
+        } else if other.is_empty() {
+            *self
+        } else {
+            Interval::new(self.start.min(other.start), self.end.max(other.end))
+        }
+    }
+
+    // The parts of self not in other, at most one piece each side of it
+    pub fn difference(&self, other: &Self) -> Vec<Self> {
+        if !self.overlaps(other) {
+            return if self.is_empty() { vec![] } else { vec![*self] };
+        }
+        [
+            Interval::new(self.start, other.start),
+            Interval::new(other.end, self.end),
+        ]
+        .into_iter()
+        .filter(|piece| !piece.is_empty())
+        .collect()
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Interval<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {})", self.start, self.end)
+    }
+}
+
+// A set of values stored as sorted, disjoint and non-adjacent intervals.
+// Inserting merges with any interval it touches.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RangeSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T: Endpoint> RangeSet<T> {
+    pub fn new() -> Self {
+        RangeSet { intervals: vec![] }
+    }
+
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    pub fn insert(&mut self, interval: Interval<T>) {
+        if interval.is_empty() {
+            return;
+        }
+        // Everything from `first` up to `last` touches the new interval
+        let first = self
+            .intervals
+            .partition_point(|existing| existing.end < interval.start);
+        let last = self
+            .intervals
+            .partition_point(|existing| existing.start <= interval.end);
+        let merged = self.intervals[first..last]
+            .iter()
+            .fold(interval, |merged, existing| merged.hull(existing));
+        self.intervals.splice(first..last, [merged]);
+    }
+
+    pub fn remove(&mut self, interval: Interval<T>) {
+        if interval.is_empty() {
+            return;
+        }
+        let first = self
+            .intervals
+            .partition_point(|existing| existing.end <= interval.start);
+        let last = self
+            .intervals
+            .partition_point(|existing| existing.start < interval.end);
+        let pieces: Vec<Interval<T>> = self.intervals[first..last]
+            .iter()
+            .flat_map(|existing| existing.difference(&interval))
+            .collect();
+        self.intervals.splice(first..last, pieces);
+    }
+
+    // The interval holding the value, found with a binary search
+    pub fn find(&self, value: T) -> Option<&Interval<T>> {
+        let index = self
+            .intervals
+            .partition_point(|existing| existing.end <= value);
+        self.intervals
+            .get(index)
+            .filter(|existing| existing.contains(value))
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.find(value).is_some()
+    }
+
+    // Whether every value of the interval is in the set
+    pub fn covers(&self, interval: &Interval<T>) -> bool {
+        interval.is_empty()
+            || self
+                .find(interval.start)
+                .is_some_and(|existing| existing.contains_interval(interval))
+    }
+
+    // The number of values in the set, None when it doesn't fit in T
+    pub fn covered_len(&self) -> Option<T> {
+        self.intervals
+            .iter()
+            .try_fold(T::default(), |total, interval| {
+                total.checked_plus(interval.len()?)
+            })
+    }
+
+    // The parts of `within` that are not in the set
+    pub fn gaps(&self, within: &Interval<T>) -> Vec<Interval<T>> {
+        let mut gaps = vec![];
+        let mut cursor = within.start;
+        for interval in &self.intervals {
+            if interval.end <= cursor {
+                continue;
+            }
+            if interval.start >= within.end {
+                break;
+            }
+            if interval.start > cursor {
+                gaps.push(Interval::new(cursor, interval.start));
+            }
+            cursor = interval.end;
+        }
+        if cursor < within.end {
+            gaps.push(Interval::new(cursor, within.end));
+        }
+        gaps
+    }
+}
+
+impl<T: Endpoint> FromIterator<Interval<T>> for RangeSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut set = RangeSet::new();
+        iter.into_iter().for_each(|interval| set.insert(interval));
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::days::common::Result;
+
+    #[test]
+    fn test_interval_relations() -> Result<()> {
+        let a = Interval::inclusive(2, 8).ok_or("No interval")?;
+        let b = Interval::inclusive(3, 7).ok_or("No interval")?;
+        let c = Interval::inclusive(9, 12).ok_or("No interval")?;
+        assert!(a.contains_interval(&b));
+        assert!(!b.contains_interval(&a));
+        assert!(a.overlaps(&b));
+        assert!(!a.overlaps(&c));
+        assert!(a.touches(&c));
+        assert_eq!(a.len(), Some(7));
+        assert_eq!(a.to_string(), "[2, 9)");
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflowing_bounds() -> Result<()> {
+        assert_eq!(Interval::inclusive(0, u32::MAX), None);
+        assert_eq!(
+            Interval::inclusive(0, u32::MAX - 1),
+            Some(Interval::new(0, u32::MAX))
+        );
+        assert_eq!(Interval::inclusive(i8::MIN, i8::MAX), None);
+        assert_eq!(Interval::new(0, u32::MAX).len(), Some(u32::MAX));
+        assert_eq!(Interval::new(-100i8, 100).len(), None);
+        assert_eq!(Interval::new(100i8, -100).len(), Some(0));
+        let set: RangeSet<i8> = [Interval::new(-100, 0), Interval::new(1, 100)]
+            .into_iter()
+            .collect();
+        assert_eq!(set.covered_len(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_interval_algebra() -> Result<()> {
+        let a = Interval::new(0, 10);
+        let b = Interval::new(5, 15);
+        assert_eq!(a.intersection(&b), Some(Interval::new(5, 10)));
+        assert_eq!(a.union(&b), Some(Interval::new(0, 15)));
+        assert_eq!(a.union(&Interval::new(11, 12)), None);
+        assert_eq!(a.difference(&b), [Interval::new(0, 5)]);
+        assert_eq!(
+            a.difference(&Interval::new(3, 4)),
+            [Interval::new(0, 3), Interval::new(4, 10)]
+        );
+        assert_eq!(a.difference(&Interval::new(-5, 20)), []);
+        assert_eq!(Interval::new(5, 5).intersection(&a), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_set_merges() -> Result<()> {
+        let set: RangeSet<i64> = [
+            Interval::new(10, 12),
+            Interval::new(0, 3),
+            Interval::new(3, 5),
+            Interval::new(11, 20),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            set.intervals(),
+            [Interval::new(0, 5), Interval::new(10, 20)]
+        );
+        assert_eq!(set.covered_len(), Some(15));
+        assert!(set.contains(4));
+        assert!(!set.contains(5));
+        assert!(set.covers(&Interval::new(12, 20)));
+        assert!(!set.covers(&Interval::new(4, 11)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_set_gaps_and_remove() -> Result<()> {
+        let mut set: RangeSet<u32> = [Interval::new(2, 4), Interval::new(6, 8)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            set.gaps(&Interval::new(0, 10)),
+            [
+                Interval::new(0, 2),
+                Interval::new(4, 6),
+                Interval::new(8, 10)
+            ]
+        );
+        assert_eq!(set.gaps(&Interval::new(3, 7)), [Interval::new(4, 6)]);
+        set.remove(Interval::new(3, 7));
+        assert_eq!(set.intervals(), [Interval::new(2, 3), Interval::new(7, 8)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+// A small seeded generator for tests and benches, so generated inputs are
+// the same on every run without pulling in a crate for it. Each item is the
+// top 31 bits of a 64 bit linear congruential generator.
+#[derive(Clone, Debug)]
+pub struct Lcg {
+    state: u64,
+}
+
+impl Lcg {
+    pub fn new(seed: u64) -> Lcg {
+        Lcg { state: seed }
+    }
+
+    // The next value in 0..n
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next().unwrap_or_default() % n
+    }
+}
+
+impl Iterator for Lcg {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        Some(self.state >> 33)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_values() {
+        let first: Vec<u64> = Lcg::new(8).take(100).collect();
+        let second: Vec<u64> = Lcg::new(8).take(100).collect();
+        assert_eq!(first, second);
+        assert_ne!(first, Lcg::new(6).take(100).collect::<Vec<_>>());
+        assert!(first.iter().all(|value| *value < 1 << 31));
+    }
+
+    #[test]
+    fn test_below() {
+        let mut lcg = Lcg::new(6);
+        let digits: Vec<u64> = (0..1000).map(|_| lcg.below(10)).collect();
+        assert!(digits.iter().all(|digit| *digit < 10));
+        assert!((0..10).all(|digit| digits.contains(&digit)));
+    }
+}
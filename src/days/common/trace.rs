@@ -0,0 +1,284 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{BufRead, Write},
+    str::FromStr,
+};
+
+use super::Result;
+
+// One structured change made by a simulation step. `kind` names what changed,
+// `id` which entity it happened to and `values` its new state. For example a
+// rope knot moving to (3, -2) is `knot 4 3 -2`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub step: u64,
+    pub kind: String,
+    pub id: i64,
+    pub values: Vec<i64>,
+}
+
+impl Event {
+    pub fn new(step: u64, kind: &str, id: i64, values: &[i64]) -> Self {
+        Event {
+            step,
+            kind: kind.to_string(),
+            id,
+            values: values.to_vec(),
+        }
+    }
+}
+
+// Line format: "<step> <kind> <id> <values>..." all space separated
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.step, self.kind, self.id)?;
+        for value in &self.values {
+            write!(f, " {value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Event {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let mut parts = line.split_whitespace();
+        let mut next = || parts.next().ok_or(format!("Truncated event '{line}'."));
+        let step = next()?.parse()?;
+        let kind = next()?.to_string();
+        let id = next()?.parse()?;
+        let values = parts
+            .map(|value| value.parse())
+            .collect::<core::result::Result<_, _>>()?;
+        Ok(Event {
+            step,
+            kind,
+            id,
+            values,
+        })
+    }
+}
+
+// Sink for the events a simulation produces, see `Trace` and `NoTrace`.
+pub trait Tracer {
+    fn record(&mut self, event: Event);
+}
+
+// Discards every event, for running a traceable simulation normally.
+pub struct NoTrace;
+
+impl Tracer for NoTrace {
+    fn record(&mut self, _event: Event) {}
+}
+
+// An in-memory recording of a simulation run, ordered by step.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    events: Vec<Event>,
+}
+
+impl Tracer for Trace {
+    fn record(&mut self, event: Event) {
+        self.events.push(event);
+    }
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace::default()
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    // The number of the last recorded step
+    pub fn last_step(&self) -> Option<u64> {
+        self.events.last().map(|event| event.step)
+    }
+
+    // All the events of a single step
+    pub fn step(&self, step: u64) -> &[Event] {
+        let start = self.events.partition_point(|event| event.step < step);
+        let end = self.events.partition_point(|event| event.step <= step);
+        &self.events[start..end]
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> Result<()> {
+        for event in &self.events {
+            writeln!(out, "{event}")?;
+        }
+        Ok(())
+    }
+
+    // Read a trace back, skipping blank lines. Steps must not decrease.
+    pub fn read(input: impl BufRead) -> Result<Trace> {
+        let mut trace = Trace::new();
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: Event = line.parse()?;
+            if trace.last_step().is_some_and(|last| event.step < last) {
+                return Err(format!("Step goes backwards at '{line}'.").into());
+            }
+            trace.record(event);
+        }
+        Ok(trace)
+    }
+}
+
+// Replays a trace step by step. The state is the latest values recorded for
+// every (kind, id) pair, i.e. where each knot, monkey or grain is.
+pub struct Debugger<'a> {
+    trace: &'a Trace,
+    position: usize,
+    step: Option<u64>,
+    state: BTreeMap<(String, i64), Vec<i64>>,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(trace: &'a Trace) -> Self {
+        Debugger {
+            trace,
+            position: 0,
+            step: None,
+            state: BTreeMap::new(),
+        }
+    }
+
+    // The last step applied, None before the first
+    pub fn current_step(&self) -> Option<u64> {
+        self.step
+    }
+
+    pub fn state(&self) -> &BTreeMap<(String, i64), Vec<i64>> {
+        &self.state
+    }
+
+    pub fn get(&self, kind: &str, id: i64) -> Option<&[i64]> {
+        self.state
+            .get(&(kind.to_string(), id))
+            .map(|values| values.as_slice())
+    }
+
+    // Apply the next step, returning its events. None at the end of the trace.
+    pub fn step_forward(&mut self) -> Option<&'a [Event]> {
+        let step = self.trace.events.get(self.position)?.step;
+        let events = self.trace.step(step);
+        for event in events {
+            self.state
+                .insert((event.kind.clone(), event.id), event.values.clone());
+        }
+        self.position += events.len();
+        self.step = Some(step);
+        Some(events)
+    }
+
+    // Move to the state right after `step`, replaying from the start when
+    // seeking backwards.
+    pub fn seek(&mut self, step: u64) {
+        if self.step.is_some_and(|current| current > step) {
+            *self = Debugger::new(self.trace);
+        }
+        while self
+            .trace
+            .events
+            .get(self.position)
+            .is_some_and(|event| event.step <= step)
+        {
+            self.step_forward();
+        }
+    }
+}
+
+// Steps where the two traces recorded different events, paired with what
+// each side recorded.
+pub fn diff<'a>(a: &'a Trace, b: &'a Trace) -> Vec<(u64, &'a [Event], &'a [Event])> {
+    let mut steps: Vec<u64> = a
+        .events
+        .iter()
+        .chain(b.events.iter())
+        .map(|event| event.step)
+        .collect();
+    steps.sort_unstable();
+    steps.dedup();
+    steps
+        .into_iter()
+        .map(|step| (step, a.step(step), b.step(step)))
+        .filter(|(_, a_events, b_events)| a_events != b_events)
+        .collect()
+}
+
+// The first step where two implementations stop agreeing
+pub fn first_divergence(a: &Trace, b: &Trace) -> Option<u64> {
+    diff(a, b).first().map(|(step, _, _)| *step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Trace {
+        let mut trace = Trace::new();
+        trace.record(Event::new(0, "knot", 0, &[1, 0]));
+        trace.record(Event::new(1, "knot", 0, &[2, 0]));
+        trace.record(Event::new(1, "knot", 1, &[1, 0]));
+        trace.record(Event::new(2, "knot", 0, &[2, 1]));
+        trace
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let mut out = vec![];
+        sample().write(&mut out)?;
+        assert_eq!(
+            String::from_utf8(out.clone())?,
+            "0 knot 0 1 0\n1 knot 0 2 0\n1 knot 1 1 0\n2 knot 0 2 1\n"
+        );
+        assert_eq!(Trace::read(out.as_slice())?, sample());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_rejects_bad_lines() -> Result<()> {
+        assert!(Trace::read("0 knot".as_bytes()).is_err());
+        assert!(Trace::read("1 knot 0 1\n0 knot 0 2".as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_debugger_seek() -> Result<()> {
+        let trace = sample();
+        let mut debugger = Debugger::new(&trace);
+        debugger.seek(1);
+        assert_eq!(debugger.current_step(), Some(1));
+        assert_eq!(debugger.get("knot", 0), Some(&[2, 0][..]));
+        assert_eq!(debugger.get("knot", 1), Some(&[1, 0][..]));
+        debugger.seek(0);
+        assert_eq!(debugger.get("knot", 1), None);
+        assert_eq!(debugger.step_forward().map(|events| events.len()), Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_divergence() -> Result<()> {
+        let a = sample();
+        let mut b = sample();
+        assert_eq!(first_divergence(&a, &b), None);
+        b.events[2].values = vec![1, 1];
+        assert_eq!(first_divergence(&a, &b), Some(1));
+        b.events.pop();
+        assert_eq!(
+            diff(&a, &b)
+                .iter()
+                .map(|(step, _, _)| *step)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        Ok(())
+    }
+}
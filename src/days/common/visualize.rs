@@ -0,0 +1,520 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use ndarray::ArrayView2;
+
+use super::Result;
+
+// A single character grid snapshot of a simulation. Renderers decide how each
+// character is drawn, so solvers only need to pick meaningful symbols.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    cells: Vec<char>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize, fill: char) -> Self {
+        Frame {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+
+    // Build a frame from text rows, padding short rows with spaces.
+    pub fn from_rows<S: AsRef<str>>(rows: impl IntoIterator<Item = S>) -> Self {
+        let rows: Vec<Vec<char>> = rows
+            .into_iter()
+            .map(|row| row.as_ref().chars().collect())
+            .collect();
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut frame = Frame::new(width, rows.len(), ' ');
+        for (y, row) in rows.into_iter().enumerate() {
+            for (x, cell) in row.into_iter().enumerate() {
+                frame.set(x, y, cell);
+            }
+        }
+        frame
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<char> {
+        if x < self.width && y < self.height {
+            Some(self.cells[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    // Out of bounds writes are ignored so callers can draw clipped shapes.
+    pub fn set(&mut self, x: usize, y: usize, cell: char) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = cell;
+        }
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = String> + '_ {
+        self.cells
+            .chunks(self.width.max(1))
+            .take(self.height)
+            .map(|row| row.iter().collect())
+    }
+
+    // Copy of this frame grown to the given size, new cells filled with `fill`.
+    fn padded(&self, width: usize, height: usize, fill: char) -> Frame {
+        let mut frame = Frame::new(width, height, fill);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                frame.set(x, y, self.cells[y * self.width + x]);
+            }
+        }
+        frame
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<String> = self.rows().collect();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
+
+impl From<ArrayView2<'_, char>> for Frame {
+    fn from(array: ArrayView2<'_, char>) -> Self {
+        Frame::from_rows(
+            array
+                .rows()
+                .into_iter()
+                .map(|row| row.iter().collect::<String>()),
+        )
+    }
+}
+
+// Sink for the frames a solver emits while it runs. Each renderer below is
+// one of these, so a solver only ever sees `&mut dyn Visualize`.
+pub trait Visualize {
+    fn emit(&mut self, frame: &Frame) -> Result<()>;
+
+    // Called once after the last frame so buffered renderers can flush.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Animate frames in place on the terminal using ANSI escapes.
+pub struct Terminal {
+    delay: Duration,
+}
+
+impl Terminal {
+    pub fn new(delay: Duration) -> Self {
+        Terminal { delay }
+    }
+}
+
+impl Visualize for Terminal {
+    fn emit(&mut self, frame: &Frame) -> Result<()> {
+        let mut out = io::stdout().lock();
+        // Move the cursor home and clear the screen below it
+        writeln!(out, "\x1b[H\x1b[J{frame}")?;
+        out.flush()?;
+        thread::sleep(self.delay);
+        Ok(())
+    }
+}
+
+// Dump every frame as plain text, separated by a numbered header.
+pub struct TextDump<W: Write> {
+    out: W,
+    count: usize,
+}
+
+impl<W: Write> TextDump<W> {
+    pub fn new(out: W) -> Self {
+        TextDump { out, count: 0 }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> Visualize for TextDump<W> {
+    fn emit(&mut self, frame: &Frame) -> Result<()> {
+        writeln!(self.out, "Frame {}", self.count)?;
+        writeln!(self.out, "{frame}\n")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    Gif,
+}
+
+// Write frames to disk as numbered PPM or PNG images, or as a single animated
+// GIF. Every cell is drawn as a `scale` x `scale` block of its palette colour.
+pub struct ImageSequence {
+    dir: PathBuf,
+    format: ImageFormat,
+    scale: usize,
+    delay: Duration,
+    count: usize,
+    // GIF frames are buffered since the encoder needs a shared palette
+    frames: Vec<Frame>,
+}
+
+impl ImageSequence {
+    pub fn new(dir: &Path, format: ImageFormat, scale: usize, delay: Duration) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(ImageSequence {
+            dir: dir.to_path_buf(),
+            format,
+            scale: scale.max(1),
+            delay,
+            count: 0,
+            frames: vec![],
+        })
+    }
+}
+
+impl Visualize for ImageSequence {
+    fn emit(&mut self, frame: &Frame) -> Result<()> {
+        let extension = match self.format {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+            ImageFormat::Gif => {
+                self.frames.push(frame.clone());
+                return Ok(());
+            }
+        };
+        let path = self
+            .dir
+            .join(format!("frame_{:05}.{extension}", self.count));
+        let mut out = BufWriter::new(File::create(path)?);
+        let (width, height, pixels) = rasterize(frame, self.scale);
+        match self.format {
+            ImageFormat::Ppm => write_ppm(&mut out, width, height, &pixels)?,
+            _ => write_png(&mut out, width, height, &pixels)?,
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.format == ImageFormat::Gif && !self.frames.is_empty() {
+            let mut out = BufWriter::new(File::create(self.dir.join("animation.gif"))?);
+            write_gif(&mut out, &self.frames, self.scale, self.delay)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// Build a renderer from its command line name.
+pub fn renderer(name: &str, dir: &Path, delay: Duration) -> Result<Box<dyn Visualize>> {
+    let format = match name {
+        "terminal" => return Ok(Box::new(Terminal::new(delay))),
+        "text" => return Ok(Box::new(TextDump::new(io::stdout()))),
+        "ppm" => ImageFormat::Ppm,
+        "png" => ImageFormat::Png,
+        "gif" => ImageFormat::Gif,
+        _ => return Err(format!("Unknown renderer '{name}'.").into()),
+    };
+    Ok(Box::new(ImageSequence::new(dir, format, 4, delay)?))
+}
+
+// Colour used for a cell. Common simulation symbols get fixed colours, heights
+// and digits get a gradient and anything else is derived from its code point.
+fn colour(cell: char) -> [u8; 3] {
+    match cell {
+        ' ' | '.' => [16, 16, 24],
+        '#' => [200, 200, 200],
+        'o' => [230, 190, 80],
+        'H' => [220, 50, 50],
+        'a'..='z' => {
+            let level = (cell as u8 - b'a') as u32 * 255 / 25;
+            [level as u8 / 2, 140 - level as u8 / 3, 255 - level as u8]
+        }
+        '0'..='9' => {
+            let level = (cell as u8 - b'0') * 25;
+            [40 + level, 120 + level / 2, 40]
+        }
+        _ => {
+            let code = cell as u32;
+            [
+                (code.wrapping_mul(97) % 200 + 55) as u8,
+                (code.wrapping_mul(57) % 200 + 55) as u8,
+                (code.wrapping_mul(31) % 200 + 55) as u8,
+            ]
+        }
+    }
+}
+
+// Expand a frame into an RGB pixel buffer.
+fn rasterize(frame: &Frame, scale: usize) -> (usize, usize, Vec<u8>) {
+    let (width, height) = (frame.width * scale, frame.height * scale);
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            pixels.extend(colour(frame.cells[(y / scale) * frame.width + x / scale]));
+        }
+    }
+    (width, height, pixels)
+}
+
+pub fn write_ppm(out: &mut impl Write, width: usize, height: usize, pixels: &[u8]) -> Result<()> {
+    write!(out, "P6\n{width} {height}\n255\n")?;
+    out.write_all(pixels)?;
+    Ok(())
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in bytes {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_png_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut body = kind.to_vec();
+    body.extend(data);
+    out.write_all(&body)?;
+    out.write_all(&crc32(&body).to_be_bytes())?;
+    Ok(())
+}
+
+// Minimal PNG encoder. The image data is stored with uncompressed deflate
+// blocks, which keeps the encoder tiny at the cost of file size.
+fn write_png(out: &mut impl Write, width: usize, height: usize, pixels: &[u8]) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Err("Can't write an empty image as a PNG.".into());
+    }
+    let too_big = |_| "Image too big for a PNG.";
+    let (png_width, png_height) = (
+        u32::try_from(width).map_err(too_big)?,
+        u32::try_from(height).map_err(too_big)?,
+    );
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = vec![];
+    header.extend(png_width.to_be_bytes());
+    header.extend(png_height.to_be_bytes());
+    // 8 bit depth, truecolour, default compression, filter and no interlace
+    header.extend([8, 2, 0, 0, 0]);
+    write_png_chunk(out, b"IHDR", &header)?;
+
+    // Every scanline is prefixed with filter type 0
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in pixels.chunks(width * 3) {
+        raw.push(0);
+        raw.extend(row);
+    }
+    let mut data = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        data.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        data.push(u8::from(blocks.peek().is_none()));
+        data.extend(len.to_le_bytes());
+        data.extend((!len).to_le_bytes());
+        data.extend(block);
+    }
+    data.extend(adler32(&raw).to_be_bytes());
+    write_png_chunk(out, b"IDAT", &data)?;
+    write_png_chunk(out, b"IEND", &[])
+}
+
+// Minimal animated GIF encoder. Frames share one palette built from the cells
+// they use and are padded to the largest frame. The LZW stream only ever
+// emits literal codes, resetting the table before it would need 10 bit codes.
+fn write_gif(out: &mut impl Write, frames: &[Frame], scale: usize, delay: Duration) -> Result<()> {
+    let width = frames.iter().map(Frame::width).max().unwrap_or(0);
+    let height = frames.iter().map(Frame::height).max().unwrap_or(0);
+    let mut palette: HashMap<char, u8> = HashMap::new();
+    let mut colours: Vec<[u8; 3]> = vec![colour(' ')];
+    palette.insert(' ', 0);
+    for cell in frames.iter().flat_map(|frame| frame.cells.iter()) {
+        if !palette.contains_key(cell) {
+            if colours.len() == 256 {
+                return Err("Too many distinct cells for a GIF palette.".into());
+            }
+            palette.insert(*cell, colours.len() as u8);
+            colours.push(colour(*cell));
+        }
+    }
+    if width == 0 || height == 0 || scale == 0 {
+        return Err("Can't write an empty image as a GIF.".into());
+    }
+    let too_big = |_| "Image too big for a GIF.";
+    let image_width = u16::try_from(width * scale).map_err(too_big)?;
+    let image_height = u16::try_from(height * scale).map_err(too_big)?;
+
+    out.write_all(b"GIF89a")?;
+    out.write_all(&image_width.to_le_bytes())?;
+    out.write_all(&image_height.to_le_bytes())?;
+    // Global colour table of 256 entries, no background colour or aspect ratio
+    out.write_all(&[0xF7, 0, 0])?;
+    for index in 0..256 {
+        out.write_all(colours.get(index).unwrap_or(&[0, 0, 0]))?;
+    }
+    // Netscape extension to loop forever
+    out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+    let centiseconds = (delay.as_millis() / 10) as u16;
+    for frame in frames {
+        let frame = frame.padded(width, height, ' ');
+        out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        out.write_all(&centiseconds.to_le_bytes())?;
+        out.write_all(&[0x00, 0x00])?;
+        out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        out.write_all(&image_width.to_le_bytes())?;
+        out.write_all(&image_height.to_le_bytes())?;
+        out.write_all(&[0x00, 0x08])?;
+
+        let indices = (0..height * scale).flat_map(|y| {
+            let frame = &frame;
+            let palette = &palette;
+            (0..width * scale).map(move |x| palette[&frame.cells[(y / scale) * width + x / scale]])
+        });
+        let data = lzw_literals(indices);
+        for block in data.chunks(255) {
+            out.write_all(&[block.len() as u8])?;
+            out.write_all(block)?;
+        }
+        out.write_all(&[0x00])?;
+    }
+    out.write_all(&[0x3B])?;
+    Ok(())
+}
+
+// Pack palette indices as 9 bit LZW codes, least significant bit first.
+fn lzw_literals(indices: impl Iterator<Item = u8>) -> Vec<u8> {
+    const CLEAR: u32 = 256;
+    const END: u32 = 257;
+    let mut bytes = vec![];
+    let (mut buffer, mut bits) = (0u32, 0);
+    let mut push = |code: u32, bytes: &mut Vec<u8>| {
+        buffer |= code << bits;
+        bits += 9;
+        while bits >= 8 {
+            bytes.push(buffer as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+    };
+    for (count, index) in indices.enumerate() {
+        if count % 254 == 0 {
+            push(CLEAR, &mut bytes);
+        }
+        push(index as u32, &mut bytes);
+    }
+    push(END, &mut bytes);
+    if bits > 0 {
+        bytes.push(buffer as u8);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_from_rows() -> Result<()> {
+        let frame = Frame::from_rows(["#.", "#"]);
+        assert_eq!((frame.width(), frame.height()), (2, 2));
+        assert_eq!(frame.get(1, 1), Some(' '));
+        assert_eq!(frame.to_string(), "#.\n# ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_dump() -> Result<()> {
+        let mut dump = TextDump::new(vec![]);
+        dump.emit(&Frame::from_rows(["ab"]))?;
+        dump.emit(&Frame::from_rows(["cd"]))?;
+        dump.finish()?;
+        let text = String::from_utf8(dump.into_inner())?;
+        assert_eq!(text, "Frame 0\nab\n\nFrame 1\ncd\n\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_png() -> Result<()> {
+        let (width, height, pixels) = rasterize(&Frame::from_rows(["#.", ".#"]), 2);
+        let mut out = vec![];
+        write_png(&mut out, width, height, &pixels)?;
+        assert!(out.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(out.ends_with(&crc32(b"IEND").to_be_bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_sizes_out_of_range() -> Result<()> {
+        let mut out = vec![];
+        assert!(write_png(&mut out, 0, 0, &[]).is_err());
+        assert!(write_png(&mut out, 3, 0, &[]).is_err());
+        let empty = Frame::new(0, 0, '.');
+        assert!(write_gif(&mut out, &[empty], 1, Duration::ZERO).is_err());
+        assert!(write_gif(&mut out, &[], 1, Duration::ZERO).is_err());
+        let wide = Frame::new(20_000, 1, '.');
+        let error = write_gif(&mut out, &[wide], 4, Duration::ZERO).unwrap_err();
+        assert_eq!(error.to_string(), "Image too big for a GIF.");
+        assert!(out.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_gif() -> Result<()> {
+        let frames = [Frame::from_rows(["#."]), Frame::from_rows(["o", "#"])];
+        let mut out = vec![];
+        write_gif(&mut out, &frames, 1, Duration::from_millis(100))?;
+        assert!(out.starts_with(b"GIF89a\x02\x00\x02\x00"));
+        assert_eq!(out.last(), Some(&0x3B));
+        Ok(())
+    }
+}
@@ -1,61 +1,241 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
 use crate::days::common::{get_input_lines, Result};
 
 pub fn solution() -> Result<()> {
     println!("~~~~~~~~~~~~~ Day 1 ~~~~~~~~~~~~~");
     let input = get_input_lines("day1")?;
-    let calorie_counts = parse_input(input);
-    println!("Part 1: {}", part1(&calorie_counts).ok_or("Empty list.")?);
-    println!("Part 2: {}", part2(calorie_counts));
+    let elves = parse_input(input)?;
+    println!("Part 1: {}", part1(&elves).ok_or("Empty list.")?);
+    println!(
+        "Part 2: {}",
+        part2(&elves).ok_or("Top 3 calorie total overflowed.")?
+    );
     Ok(())
 }
 
 // Find the maximum calorie count
-fn part1(calorie_counts: &Vec<u32>) -> Option<&u32> {
-    calorie_counts.iter().max()
+fn part1(elves: &[Elf]) -> Option<u64> {
+    top_k(elves, 1).first().map(|elf| elf.total)
+}
+
+// Find the sum of the top 3 calorie counts, or None if it overflows
+fn part2(elves: &[Elf]) -> Option<u64> {
+    top_k(elves, 3)
+        .iter()
+        .try_fold(0u64, |total, elf| total.checked_add(elf.total))
 }
 
-// Find the sum of the top 3 calorie counts
-fn part2(mut calorie_counts: Vec<u32>) -> u32 {
-    calorie_counts.sort_unstable_by(|a, b| b.cmp(a));
-    calorie_counts.iter().take(3).sum::<u32>()
+// An elf's position in the input, the calories of each item it carries and
+// their total.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Elf {
+    pub index: usize,
+    pub items: Vec<u64>,
+    pub total: u64,
+}
+
+impl Elf {
+    fn new(index: usize, items: Vec<u64>) -> Result<Elf> {
+        let total = items
+            .iter()
+            .try_fold(0u64, |total, item| total.checked_add(*item))
+            .ok_or(format!("Calorie total of elf {index} overflowed."))?;
+        Ok(Elf {
+            index,
+            items,
+            total,
+        })
+    }
 }
 
 /*
-Parse input into calorie count totals. For example:
-100,33, ,25,25,50, ,10 --> [133, 75, 10]
+Parse input into elves. Blank lines separate elves, for example:
+100,33, ,25,25,50, ,10 --> [[100, 33], [25, 25, 50], [10]]
 
-Panics on missing file or bad input.
+Any other line that isn't a calorie count is an error.
 */
-fn parse_input(input: impl Iterator<Item = String>) -> Vec<u32> {
-    input
-        .fold((vec![], 0), |(mut acc, total), line| {
-            if let Ok(count) = line.parse::<u32>() {
-                (acc, total + count)
-            } else {
-                acc.push(total);
-                (acc, 0)
+fn parse_input(input: impl Iterator<Item = String>) -> Result<Vec<Elf>> {
+    let mut elves = vec![];
+    let mut items = vec![];
+    for (line_number, line) in (1..).zip(input) {
+        if line.trim().is_empty() {
+            if !items.is_empty() {
+                elves.push(Elf::new(elves.len(), items)?);
+                items = vec![];
             }
-        })
-        .0
+        } else {
+            let item = line
+                .trim()
+                .parse()
+                .map_err(|_| format!("Line {line_number}: '{line}' is not a calorie count."))?;
+            items.push(item);
+        }
+    }
+    if !items.is_empty() {
+        elves.push(Elf::new(elves.len(), items)?);
+    }
+    Ok(elves)
+}
+
+// The k elves carrying the most calories, most first. Streams through the
+// elves keeping a min heap of at most k, so nothing else is sorted. Ties go
+// to the elf earlier in the input.
+pub fn top_k<'a>(elves: impl IntoIterator<Item = &'a Elf>, k: usize) -> Vec<&'a Elf> {
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for elf in elves {
+        heap.push(Reverse(ByTotal(elf)));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(ByTotal(elf))| elf)
+        .collect()
+}
+
+// Orders elves by total, then by earliest in the input
+struct ByTotal<'a>(&'a Elf);
+
+impl ByTotal<'_> {
+    fn key(&self) -> (u64, Reverse<usize>) {
+        (self.0.total, Reverse(self.0.index))
+    }
+}
+
+impl PartialEq for ByTotal<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ByTotal<'_> {}
+
+impl PartialOrd for ByTotal<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ByTotal<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+// The smallest total that at least `percent` of the elves carry no more than,
+// using the nearest rank method.
+pub fn percentile(elves: &[Elf], percent: f64) -> Option<u64> {
+    if elves.is_empty() || !(0.0..=100.0).contains(&percent) {
+        return None;
+    }
+    let mut totals: Vec<u64> = elves.iter().map(|elf| elf.total).collect();
+    let rank = ((percent / 100.0 * totals.len() as f64).ceil() as usize).max(1);
+    Some(*totals.select_nth_unstable(rank - 1).1)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub elves: usize,
+    pub items: usize,
+    pub total: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub median: u64,
+}
+
+pub fn summarize(elves: &[Elf]) -> Option<Summary> {
+    let total = elves
+        .iter()
+        .try_fold(0u64, |total, elf| total.checked_add(elf.total))?;
+    Some(Summary {
+        elves: elves.len(),
+        items: elves.iter().map(|elf| elf.items.len()).sum(),
+        total,
+        min: elves.iter().map(|elf| elf.total).min()?,
+        max: elves.iter().map(|elf| elf.total).max()?,
+        mean: total as f64 / elves.len() as f64,
+        median: percentile(elves, 50.0)?,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn calorie_counts() -> Result<Vec<u32>> {
-        Ok(parse_input(get_input_lines("day1")?))
+    fn elves() -> Result<Vec<Elf>> {
+        parse_input(get_input_lines("day1")?)
+    }
+
+    fn sample() -> Result<Vec<Elf>> {
+        let lines = "1000\n2000\n3000\n\n4000\n\n5000\n6000\n\n7000\n8000\n9000\n\n10000";
+        parse_input(lines.lines().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_input() -> Result<()> {
+        let elves = sample()?;
+        assert_eq!(elves.len(), 5);
+        assert_eq!(elves[0], Elf::new(0, vec![1000, 2000, 3000])?);
+        assert_eq!(elves[4].total, 10000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_input_rejects_bad_lines() -> Result<()> {
+        let lines = ["100", "abc", "", "200"].map(String::from);
+        assert!(parse_input(lines.into_iter()).is_err());
+        let lines = [u64::MAX.to_string(), "1".to_string()];
+        assert!(parse_input(lines.into_iter()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_k() -> Result<()> {
+        let elves = sample()?;
+        let top: Vec<usize> = top_k(&elves, 3).iter().map(|elf| elf.index).collect();
+        assert_eq!(top, [3, 2, 4]);
+        assert_eq!(top_k(&elves, 10).len(), 5);
+        assert!(top_k(&elves, 0).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_statistics() -> Result<()> {
+        let elves = sample()?;
+        assert_eq!(percentile(&elves, 0.0), Some(4000));
+        assert_eq!(percentile(&elves, 40.0), Some(6000));
+        assert_eq!(percentile(&elves, 100.0), Some(24000));
+        let summary = summarize(&elves).ok_or("Empty list.")?;
+        assert_eq!(summary.items, 10);
+        assert_eq!(summary.total, 55000);
+        assert_eq!(
+            (summary.min, summary.max, summary.median),
+            (4000, 24000, 10000)
+        );
+        assert_eq!(summary.mean, 11000.0);
+        Ok(())
     }
 
     #[test]
     fn test_part_1_gives_correct_answer() -> Result<()> {
-        assert_eq!(part1(&calorie_counts()?).ok_or("Empty list.")?, &69836);
+        assert_eq!(part1(&elves()?).ok_or("Empty list.")?, 69836);
         Ok(())
     }
 
     #[test]
     fn test_part_2_gives_correct_answer() -> Result<()> {
-        assert_eq!(part2(calorie_counts()?), 207968);
+        assert_eq!(part2(&elves()?), Some(207968));
+        Ok(())
+    }
+
+    #[test]
+    fn test_part_2_overflow() -> Result<()> {
+        let elves = vec![Elf::new(0, vec![u64::MAX])?, Elf::new(1, vec![1])?];
+        assert_eq!(part2(&elves), None);
+        assert_eq!(part2(&elves[..1]), Some(u64::MAX));
         Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use crate::days::common::visualize::{Frame, Visualize};
 use crate::days::common::Result;
 use std::fs;
 
@@ -12,6 +13,25 @@ pub fn solution<'a>() -> Result<()> {
     Ok(())
 }
 
+// Animate the CRT drawing the screen one pixel per cycle
+pub fn visualize(vis: &mut dyn Visualize) -> Result<()> {
+    let input = fs::read_to_string(INPUT)?;
+    let instructions = input.lines().flat_map(parse_instruction).flatten();
+    let mut screen = Frame::new(40, 6, '.');
+    let mut register: i32 = 1;
+    for (cycle, instruction) in (0..240).zip(instructions) {
+        let (row, col) = (cycle / 40, cycle % 40);
+        if (col as i32 - register).abs() <= 1 {
+            screen.set(col, row, '#');
+        }
+        vis.emit(&screen)?;
+        if let Instruction::ADDX(amount) = instruction {
+            register += amount;
+        }
+    }
+    Ok(())
+}
+
 fn part1(input: &str) -> i32 {
     let instructions = input.lines().flat_map(parse_instruction).flatten();
     let mut register = 1;
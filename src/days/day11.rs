@@ -1,3 +1,4 @@
+use crate::days::common::trace::{Event, NoTrace, Trace, Tracer};
 use crate::days::common::Result;
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -12,7 +13,20 @@ pub fn solution<'a>() -> Result<()> {
     Ok(())
 }
 
+// Record every item throw of part one, one step per round. Each event is
+// keyed by the throwing monkey with the receiver and new worry level.
+pub fn trace() -> Result<Trace> {
+    let input = fs::read_to_string(INPUT)?;
+    let mut trace = Trace::new();
+    simulate(&input, 20, 3, &mut trace);
+    Ok(trace)
+}
+
 fn part_one(input: &str, rounds: u16, divisor: u32) -> u128 {
+    simulate(input, rounds, divisor, &mut NoTrace)
+}
+
+fn simulate(input: &str, rounds: u16, divisor: u32, tracer: &mut impl Tracer) -> u128 {
     let lines: Vec<&str> = input.lines().collect();
     let monkeys: Vec<RefCell<Monkey>> = lines
         .chunks(7)
@@ -20,23 +34,14 @@ fn part_one(input: &str, rounds: u16, divisor: u32) -> u128 {
         .map(|monkey| RefCell::new(monkey))
         .collect();
     let mut inspection_counts: HashMap<usize, u128> = HashMap::new();
-    for _ in 0..rounds {
-        for monkey in monkeys.iter() {
-            let mut monkey = monkey.borrow_mut();
-            let items: Vec<u32> = monkey.items.drain(..).collect();
-            for item in items {
-                inspection_counts
-                    .entry(monkey.id)
-                    .and_modify(|c| *c += 1)
-                    .or_insert(1);
-                let new_item: u32 = apply_op(&item, monkey.op) / divisor;
-                if (new_item % monkey.divisor) == u32::from(0 as u8) {
-                    monkeys[monkey.if_true].borrow_mut().items.push(new_item);
-                } else {
-                    monkeys[monkey.if_false].borrow_mut().items.push(new_item);
-                }
-            }
-        }
+    for round in 0..rounds {
+        play_round(
+            &monkeys,
+            divisor,
+            &mut inspection_counts,
+            round.into(),
+            tracer,
+        );
     }
     let mut final_inpection_counts = inspection_counts.into_values().collect::<Vec<_>>();
     final_inpection_counts.sort_by(|a, b| b.cmp(a));
@@ -47,6 +52,39 @@ fn part_one(input: &str, rounds: u16, divisor: u32) -> u128 {
         .unwrap()
 }
 
+// Every monkey in turn inspects and throws all of its items
+fn play_round(
+    monkeys: &[RefCell<Monkey>],
+    divisor: u32,
+    inspection_counts: &mut HashMap<usize, u128>,
+    round: u64,
+    tracer: &mut impl Tracer,
+) {
+    for monkey in monkeys.iter() {
+        let mut monkey = monkey.borrow_mut();
+        let items: Vec<u32> = monkey.items.drain(..).collect();
+        for item in items {
+            inspection_counts
+                .entry(monkey.id)
+                .and_modify(|c| *c += 1)
+                .or_insert(1);
+            let new_item: u32 = apply_op(&item, monkey.op) / divisor;
+            let target = if new_item.is_multiple_of(monkey.divisor) {
+                monkey.if_true
+            } else {
+                monkey.if_false
+            };
+            tracer.record(Event::new(
+                round,
+                "throw",
+                monkey.id as i64,
+                &[target as i64, new_item.into()],
+            ));
+            monkeys[target].borrow_mut().items.push(new_item);
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
 struct Monkey {
     id: usize,
@@ -114,6 +152,51 @@ mod tests {
         Ok(())
     }
 
+    const SAMPLE: &str = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+";
+
+    #[test]
+    fn test_sample() -> Result<()> {
+        assert_eq!(part_one(SAMPLE, 20, 3), 10605);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_first_round() -> Result<()> {
+        let mut trace = Trace::new();
+        simulate(SAMPLE, 1, 3, &mut trace);
+        assert_eq!(trace.last_step(), Some(0));
+        assert_eq!(trace.events()[0].to_string(), "0 throw 0 3 500");
+        assert_eq!(trace.events()[1].to_string(), "0 throw 0 3 620");
+        Ok(())
+    }
+
     #[test]
     fn test_parse_monkey() -> Result<()> {
         let chunk = [
@@ -1,6 +1,7 @@
+use crate::days::common::visualize::{Frame, Visualize};
 use crate::days::common::Result;
 use std::{
-    collections::{BinaryHeap, HashMap},
+    collections::{BinaryHeap, HashMap, HashSet},
     fs,
 };
 
@@ -16,6 +17,48 @@ pub fn solution<'a>() -> Result<()> {
     Ok(())
 }
 
+// Animate a breadth first search from the start, one frame per distance. The
+// explored area is drawn as '.' and the frontier as '@'.
+pub fn visualize(vis: &mut dyn Visualize) -> Result<()> {
+    let input = fs::read_to_string(INPUT)?;
+    let grid_rows = parse_rows(&input);
+    let (start, end) = find_start_end(&grid_rows);
+    let (start, end) = (start.ok_or("No start.")?, end.ok_or("No end.")?);
+    let mut frame = Frame::from_rows(grid_rows.iter().map(|row| {
+        row.iter()
+            .map(|node| match node.value {
+                '`' => 'S',
+                '{' => 'E',
+                value => value,
+            })
+            .collect::<String>()
+    }));
+    let graph = build_graph(grid_rows);
+
+    let mut seen = HashSet::from([start]);
+    let mut frontier = vec![start];
+    while !frontier.is_empty() {
+        for node in &frontier {
+            frame.set(node.c_index, node.r_index, '@');
+        }
+        vis.emit(&frame)?;
+        if frontier.contains(&end) {
+            break;
+        }
+        let mut next_frontier = vec![];
+        for node in &frontier {
+            frame.set(node.c_index, node.r_index, '.');
+            for next_node in graph.get(node).into_iter().flatten() {
+                if seen.insert(*next_node) {
+                    next_frontier.push(*next_node);
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+    Ok(())
+}
+
 fn part_one(input: &str) -> Option<usize> {
     let grid_rows = parse_rows(input);
     let (start, end) = find_start_end(&grid_rows);
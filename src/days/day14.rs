@@ -1,7 +1,10 @@
+use crate::days::common::trace::{Event, NoTrace, Trace, Tracer};
+use crate::days::common::visualize::{Frame, Visualize};
 use crate::days::common::Result;
 use std::collections::HashSet;
 use std::fs;
 
+use ndarray::s;
 use ndarray::Array2;
 use ndarray::ArrayView2;
 use nom::character::complete::char;
@@ -25,14 +28,34 @@ pub fn solution() -> Result<()> {
     Ok(())
 }
 
+// Animate part one, emitting the cave each time a grain of sand settles
+pub fn visualize(vis: &mut dyn Visualize) -> Result<()> {
+    let input = fs::read_to_string(INPUT)?;
+    let mut scan = render_scan(&input, 0);
+    vis.emit(&cave_frame(scan.view()))?;
+    while let Some((next, _)) = drop_grain(scan) {
+        scan = next;
+        vis.emit(&cave_frame(scan.view()))?;
+    }
+    Ok(())
+}
+
+// Record where every grain of part one settles, one step per grain
+pub fn trace() -> Result<Trace> {
+    let input = fs::read_to_string(INPUT)?;
+    let mut trace = Trace::new();
+    simulate_sand(render_scan(&input, 0), &mut trace);
+    Ok(trace)
+}
+
 fn part_one(input: &str) -> i32 {
     let scan = render_scan(input, 0);
-    simulate_sand(scan)
+    simulate_sand(scan, &mut NoTrace)
 }
 
 fn part_two(input: &str) -> i32 {
     let scan = render_scan(input, 150);
-    simulate_sand(scan)
+    simulate_sand(scan, &mut NoTrace)
 }
 
 fn render_scan(input: &str, padding: i32) -> Array2<char> {
@@ -63,24 +86,27 @@ fn render_scan(input: &str, padding: i32) -> Array2<char> {
     )
 }
 
-fn simulate_sand(mut scan: Array2<char>) -> i32 {
+fn simulate_sand(mut scan: Array2<char>, tracer: &mut impl Tracer) -> i32 {
     let mut count = 0;
-    for i in 0.. {
-        scan = if let Some(scan) = add_sand(500, scan) {
-            if let Some(scan) = move_sand(0, 500, scan) {
-                scan
-            } else {
-                count = i;
-                break;
-            }
-        } else {
-            count = i;
-            break;
-        };
+    while let Some((next, (x, y))) = drop_grain(scan) {
+        tracer.record(Event::new(
+            count as u64,
+            "settle",
+            count.into(),
+            &[x.into(), y.into()],
+        ));
+        scan = next;
+        count += 1;
     }
     count
 }
 
+// Drop a single grain from the source, returning where it settled. None once
+// the source is blocked or the grain falls out of the cave.
+fn drop_grain(scan: Array2<char>) -> Option<(Array2<char>, Point)> {
+    move_sand(0, 500, add_sand(500, scan)?)
+}
+
 fn add_sand(col: usize, mut array: Array2<char>) -> Option<Array2<char>> {
     let start = array.get_mut([0, col]).unwrap();
     if start == &'o' {
@@ -91,7 +117,7 @@ fn add_sand(col: usize, mut array: Array2<char>) -> Option<Array2<char>> {
     }
 }
 
-fn move_sand(row: usize, col: usize, mut array: Array2<char>) -> Option<Array2<char>> {
+fn move_sand(row: usize, col: usize, mut array: Array2<char>) -> Option<(Array2<char>, Point)> {
     if let Some(below) = array.get_mut([row + 1, col]) {
         if below == &'.' {
             *below = 'o';
@@ -112,7 +138,7 @@ fn move_sand(row: usize, col: usize, mut array: Array2<char>) -> Option<Array2<c
                 }
             }
         }
-        Some(array)
+        Some((array, (col as i32, row as i32)))
     } else {
         None
     }
@@ -159,11 +185,15 @@ fn fill_points(points: Vec<Point>) -> Vec<Point> {
     filled_points
 }
 
-// Helper function for debugging
-fn print_cave(array: ArrayView2<char>) {
-    for row in array.rows() {
-        println!["{:?}", row.to_string()];
-    }
+// Crop the empty columns left of the rocks, sand can't settle there.
+fn cave_frame(array: ArrayView2<char>) -> Frame {
+    let left = array
+        .columns()
+        .into_iter()
+        .position(|col| col.iter().any(|c| c == &'#'))
+        .unwrap_or(0)
+        .saturating_sub(1);
+    Frame::from(array.slice(s![.., left..]))
 }
 
 #[cfg(test)]
@@ -194,6 +224,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_cave_frame() -> Result<()> {
+        let scan = render_scan("498,4 -> 498,6 -> 496,6", 0);
+        let frame = cave_frame(scan.view());
+        assert_eq!(frame.width(), 4);
+        assert_eq!(frame.get(3, 4), Some('#'));
+        assert_eq!(frame.get(0, 6), Some('.'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_sample() -> Result<()> {
+        let scan = render_scan(
+            "498,4 -> 498,6 -> 496,6\n503,4 -> 502,4 -> 502,9 -> 494,9",
+            0,
+        );
+        let mut trace = Trace::new();
+        assert_eq!(simulate_sand(scan, &mut trace), 24);
+        assert_eq!(trace.events()[0].to_string(), "0 settle 0 500 8");
+        assert_eq!(trace.events()[1].to_string(), "1 settle 1 499 8");
+        assert_eq!(trace.events()[23].to_string(), "23 settle 23 495 8");
+        Ok(())
+    }
+
     #[test]
     fn test_part_one() -> Result<()> {
         let input = fs::read_to_string(INPUT)?;
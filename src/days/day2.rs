@@ -2,117 +2,203 @@ use crate::days::common::{self, Result};
 
 pub fn solution() -> Result<()> {
     println!("~~~~~~~~~~~~~ Day 2 ~~~~~~~~~~~~~");
+    let rules = Rules::rock_paper_scissors();
     let lines = common::get_input_lines("day2")?;
-    println!("Part 1: {}", total_score(lines.map(first_parser)));
+    println!(
+        "Part 1: {}",
+        total_score(&rules, lines.map(|line| rules.parse_moves(&line)))
+    );
     let lines = common::get_input_lines("day2")?;
-    println!("Part 2: {}", total_score(lines.map(second_parser)));
+    println!(
+        "Part 2: {}",
+        total_score(&rules, lines.map(|line| rules.parse_outcomes(&line)))
+    );
     Ok(())
 }
 
-// Parse an individual line into a Round using the first interpretation
-fn first_parser(line: String) -> Option<Round> {
-    if let Some((opponent, myself)) = match line.as_str() {
-        "A X" => Some((Selection::Rock, Selection::Rock)),
-        "A Y" => Some((Selection::Rock, Selection::Paper)),
-        "A Z" => Some((Selection::Rock, Selection::Scissors)),
-        "B X" => Some((Selection::Paper, Selection::Rock)),
-        "B Y" => Some((Selection::Paper, Selection::Paper)),
-        "B Z" => Some((Selection::Paper, Selection::Scissors)),
-        "C X" => Some((Selection::Scissors, Selection::Rock)),
-        "C Y" => Some((Selection::Scissors, Selection::Paper)),
-        "C Z" => Some((Selection::Scissors, Selection::Scissors)),
-        _ => None,
-    } {
-        Some(Round { myself, opponent })
-    } else {
-        None
-    }
-}
-
-// Parse an individual line into a Round using the second interpretation
-fn second_parser(line: String) -> Option<Round> {
-    if let Some((opponent, outcome)) = match line.as_str() {
-        "A X" => Some((Selection::Rock, Outcome::Lose)),
-        "A Y" => Some((Selection::Rock, Outcome::Draw)),
-        "A Z" => Some((Selection::Rock, Outcome::Win)),
-        "B X" => Some((Selection::Paper, Outcome::Lose)),
-        "B Y" => Some((Selection::Paper, Outcome::Draw)),
-        "B Z" => Some((Selection::Paper, Outcome::Win)),
-        "C X" => Some((Selection::Scissors, Outcome::Lose)),
-        "C Y" => Some((Selection::Scissors, Outcome::Draw)),
-        "C Z" => Some((Selection::Scissors, Outcome::Win)),
-        _ => None,
-    } {
-        Some(Round {
-            myself: my_selection(&opponent, &outcome),
-            opponent,
-        })
-    } else {
-        None
+// Points for the move played and for the result of the round
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scoring {
+    pub choice: Vec<i32>,
+    pub win: i32,
+    pub draw: i32,
+    pub loss: i32,
+}
+
+impl Scoring {
+    // The puzzle's scoring, 1 point for the first move, 2 for the second and
+    // so on, plus 6 for a win and 3 for a draw.
+    pub fn standard(moves: usize) -> Scoring {
+        Scoring {
+            choice: (1..=moves as i32).collect(),
+            win: 6,
+            draw: 3,
+            loss: 0,
+        }
     }
 }
 
 /*
-Find the selction to satisify the outcome. Could embed this logic directly in
-the decoding but this is more clear.
+Rules for a cyclic game with an odd number of moves. With the moves listed in
+order, each one beats the (n - 1) / 2 moves before it and loses to the ones
+after it, wrapping around. For rock, paper, scissors: paper beats rock,
+scissors beats paper and rock beats scissors.
+
+Moves are referred to by their index in the list. In the strategy guide the
+opponent's moves are the letters from A and the second column uses the last n
+letters of the alphabet, X, Y, Z for three moves.
 */
-fn my_selection(opponent: &Selection, outcome: &Outcome) -> Selection {
-    match (opponent, outcome) {
-        (Selection::Rock, Outcome::Win) => Selection::Paper,
-        (Selection::Rock, Outcome::Lose) => Selection::Scissors,
-        (Selection::Paper, Outcome::Win) => Selection::Scissors,
-        (Selection::Paper, Outcome::Lose) => Selection::Rock,
-        (Selection::Scissors, Outcome::Win) => Selection::Rock,
-        (Selection::Scissors, Outcome::Lose) => Selection::Paper,
-        // Rest are draws
-        _ => *opponent,
-    }
+#[derive(Clone, Debug)]
+pub struct Rules {
+    moves: Vec<String>,
+    scoring: Scoring,
 }
 
-struct Round {
-    myself: Selection,
-    opponent: Selection,
+impl Rules {
+    pub fn cyclic(moves: &[&str], scoring: Scoring) -> Result<Rules> {
+        if moves.len().is_multiple_of(2) || moves.len() > 13 {
+            return Err("A cyclic game needs an odd number of moves, at most 13.".into());
+        }
+        if scoring.choice.len() != moves.len() {
+            return Err("Every move needs a choice score.".into());
+        }
+        Ok(Rules {
+            moves: moves.iter().map(|name| name.to_string()).collect(),
+            scoring,
+        })
+    }
+
+    pub fn rock_paper_scissors() -> Rules {
+        Rules::cyclic(&["Rock", "Paper", "Scissors"], Scoring::standard(3)).unwrap()
+    }
+
+    pub fn rock_paper_scissors_lizard_spock() -> Rules {
+        let moves = ["Rock", "Spock", "Paper", "Lizard", "Scissors"];
+        Rules::cyclic(&moves, Scoring::standard(5)).unwrap()
+    }
+
+    pub fn rock_paper_scissors_7() -> Rules {
+        let moves = [
+            "Rock", "Water", "Air", "Paper", "Sponge", "Scissors", "Fire",
+        ];
+        Rules::cyclic(&moves, Scoring::standard(7)).unwrap()
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn name(&self, move_: usize) -> &str {
+        &self.moves[move_]
+    }
+
+    pub fn beats(&self, winner: usize, loser: usize) -> bool {
+        let distance = (winner + self.move_count() - loser) % self.move_count();
+        (1..=self.move_count() / 2).contains(&distance)
+    }
+
+    pub fn outcome(&self, myself: usize, opponent: usize) -> Outcome {
+        if self.beats(myself, opponent) {
+            Outcome::Win
+        } else if self.beats(opponent, myself) {
+            Outcome::Lose
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    pub fn score(&self, round: &Round) -> i32 {
+        let outcome_score = match self.outcome(round.myself, round.opponent) {
+            Outcome::Win => self.scoring.win,
+            Outcome::Draw => self.scoring.draw,
+            Outcome::Lose => self.scoring.loss,
+        };
+        self.scoring.choice[round.myself] + outcome_score
+    }
+
+    fn opponent_key(&self, key: char) -> Option<usize> {
+        let index = (key as usize).checked_sub('A' as usize)?;
+        (index < self.move_count()).then_some(index)
+    }
+
+    fn response_key(&self, key: char) -> Option<usize> {
+        let first = 'Z' as usize + 1 - self.move_count();
+        let index = (key as usize).checked_sub(first)?;
+        (index < self.move_count()).then_some(index)
+    }
+
+    // Exactly "<opponent> <response>", one key either side of a single space
+    fn parse_keys(&self, line: &str) -> Option<(usize, usize)> {
+        let (opponent, response) = line.split_once(' ')?;
+        Some((
+            self.opponent_key(single_char(opponent)?)?,
+            self.response_key(single_char(response)?)?,
+        ))
+    }
+
+    // First interpretation, the second column is the move to play
+    pub fn parse_moves(&self, line: &str) -> Option<Round> {
+        self.parse(line, Interpretation::Moves)
+    }
+
+    // Second interpretation, the second column is how the round should end
+    pub fn parse_outcomes(&self, line: &str) -> Option<Round> {
+        self.parse(line, Interpretation::Outcomes)
+    }
+
+    pub fn parse(&self, line: &str, interpretation: Interpretation) -> Option<Round> {
+        let (opponent, response) = self.parse_keys(line)?;
+        Some(self.interpret(opponent, response, interpretation))
+    }
+
+    /*
+    Decide my move from the second column. For outcomes the middle key is a
+    draw, keys after it win and keys before it lose, each one further from the
+    middle picking a move that much further around the cycle. X, Y, Z are
+    lose, draw and win for three moves.
+    */
+    fn interpret(&self, opponent: usize, response: usize, interpretation: Interpretation) -> Round {
+        let myself = match interpretation {
+            Interpretation::Moves => response,
+            Interpretation::Outcomes => {
+                let offset = response + self.move_count() - self.move_count() / 2;
+                (opponent + offset) % self.move_count()
+            }
+        };
+        Round { myself, opponent }
+    }
 }
 
-#[derive(Copy, Clone)]
-enum Selection {
-    Rock,
-    Paper,
-    Scissors,
+fn single_char(key: &str) -> Option<char> {
+    let mut chars = key.chars();
+    let key = chars.next()?;
+    chars.next().is_none().then_some(key)
 }
 
-enum Outcome {
-    Win,
-    Lose,
-    Draw,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpretation {
+    Moves,
+    Outcomes,
 }
 
-fn choice_score(selection: &Selection) -> i32 {
-    return match selection {
-        Selection::Rock => 1,
-        Selection::Paper => 2,
-        Selection::Scissors => 3,
-    };
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Round {
+    myself: usize,
+    opponent: usize,
 }
 
-fn outcome_score(myself: &Selection, opponent: &Selection) -> i32 {
-    match (myself, opponent) {
-        (Selection::Rock, Selection::Paper) => 0,
-        (Selection::Rock, Selection::Scissors) => 6,
-        (Selection::Paper, Selection::Rock) => 6,
-        (Selection::Paper, Selection::Scissors) => 0,
-        (Selection::Scissors, Selection::Rock) => 0,
-        (Selection::Scissors, Selection::Paper) => 6,
-        // Rest are draws
-        _ => 3,
-    }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Lose,
+    Draw,
 }
 
-fn total_score(rounds: impl Iterator<Item = Option<Round>>) -> i32 {
+fn total_score(rules: &Rules, rounds: impl Iterator<Item = Option<Round>>) -> i32 {
     rounds
         .map(|round| {
-            if let Some(Round { myself, opponent }) = round {
-                choice_score(&myself) + outcome_score(&myself, &opponent)
+            if let Some(round) = round {
+                rules.score(&round)
             } else {
                 0
             }
@@ -120,21 +206,265 @@ fn total_score(rounds: impl Iterator<Item = Option<Round>>) -> i32 {
         .sum()
 }
 
+// How a single round of the guide compares to the best move available
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoundAnalysis {
+    pub line: usize,
+    pub round: Round,
+    pub guide: i32,
+    pub best: i32,
+    pub regret: i32,
+}
+
+pub fn analyse_rounds(
+    rules: &Rules,
+    rounds: impl Iterator<Item = Option<Round>>,
+) -> Vec<RoundAnalysis> {
+    (1..)
+        .zip(rounds)
+        .filter_map(|(line, round)| {
+            let round = round?;
+            let guide = rules.score(&round);
+            let best = (0..rules.move_count())
+                .map(|myself| {
+                    rules.score(&Round {
+                        myself,
+                        opponent: round.opponent,
+                    })
+                })
+                .max()?;
+            Some(RoundAnalysis {
+                line,
+                round,
+                guide,
+                best,
+                regret: best - guide,
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+pub fn tally(rules: &Rules, rounds: impl Iterator<Item = Option<Round>>) -> Tally {
+    rounds.flatten().fold(Tally::default(), |mut tally, round| {
+        match rules.outcome(round.myself, round.opponent) {
+            Outcome::Win => tally.wins += 1,
+            Outcome::Draw => tally.draws += 1,
+            Outcome::Lose => tally.losses += 1,
+        }
+        tally
+    })
+}
+
+// All orderings of 0..n, in lexicographic order
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    permutations(n - 1)
+        .into_iter()
+        .flat_map(|shorter| {
+            (0..n).map(move |position| {
+                let mut longer = shorter.clone();
+                longer.insert(position, n - 1);
+                longer
+            })
+        })
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/*
+Suppose we don't know which second column key means what. Score the guide with
+every way of assigning the keys, e.g. X -> Z, Y -> X, Z -> Y, and return each
+assignment's total alongside their mean, the expected score if the encoding
+were a uniformly random permutation. Three moves give 6 assignments.
+*/
+pub fn permutation_scores(
+    rules: &Rules,
+    lines: &[String],
+    interpretation: Interpretation,
+) -> (Vec<(Vec<usize>, i32)>, f64) {
+    let keys: Vec<(usize, usize)> = lines
+        .iter()
+        .flat_map(|line| rules.parse_keys(line))
+        .collect();
+    let scores: Vec<(Vec<usize>, i32)> = permutations(rules.move_count())
+        .into_iter()
+        .map(|mapping| {
+            let total = keys
+                .iter()
+                .map(|(opponent, response)| {
+                    rules.score(&rules.interpret(*opponent, mapping[*response], interpretation))
+                })
+                .sum();
+            (mapping, total)
+        })
+        .collect();
+    let expected = scores.iter().map(|(_, total)| *total as f64).sum::<f64>() / scores.len() as f64;
+    (scores, expected)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rock_paper_scissors_rules() -> Result<()> {
+        let rules = Rules::rock_paper_scissors();
+        assert!(rules.beats(1, 0));
+        assert!(rules.beats(2, 1));
+        assert!(rules.beats(0, 2));
+        assert_eq!(rules.outcome(0, 0), Outcome::Draw);
+        assert_eq!(
+            rules.parse_moves("A Y"),
+            Some(Round {
+                myself: 1,
+                opponent: 0
+            })
+        );
+        assert_eq!(
+            rules.parse_outcomes("A Y"),
+            Some(Round {
+                myself: 0,
+                opponent: 0
+            })
+        );
+        assert_eq!(
+            rules.parse_outcomes("B X"),
+            Some(Round {
+                myself: 0,
+                opponent: 1
+            })
+        );
+        assert_eq!(
+            rules.parse_outcomes("C Z"),
+            Some(Round {
+                myself: 0,
+                opponent: 2
+            })
+        );
+        assert_eq!(rules.parse_moves("D X"), None);
+        assert_eq!(rules.parse_moves("A W"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample() -> Result<()> {
+        let rules = Rules::rock_paper_scissors();
+        let lines = ["A Y", "B X", "C Z"];
+        assert_eq!(
+            total_score(&rules, lines.iter().map(|l| rules.parse_moves(l))),
+            15
+        );
+        assert_eq!(
+            total_score(&rules, lines.iter().map(|l| rules.parse_outcomes(l))),
+            12
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_lines() -> Result<()> {
+        let rules = Rules::rock_paper_scissors();
+        for line in [
+            "AXY", "A-Y", "AY", "A  Y", "A Y ", " A Y", "AB Y", "A YZ", "A", "",
+        ] {
+            assert_eq!(rules.parse_moves(line), None, "{line:?}");
+        }
+        assert!(rules.parse_moves("A Y").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyse_rounds() -> Result<()> {
+        let rules = Rules::rock_paper_scissors();
+        let lines = ["A Y", "B X", "", "C Z"];
+        let analysis = analyse_rounds(&rules, lines.iter().map(|l| rules.parse_moves(l)));
+        let summary: Vec<_> = analysis
+            .iter()
+            .map(|a| (a.line, a.guide, a.best, a.regret))
+            .collect();
+        assert_eq!(summary, [(1, 8, 8, 0), (2, 1, 9, 8), (4, 6, 7, 1)]);
+        let moves = tally(&rules, lines.iter().map(|l| rules.parse_moves(l)));
+        assert_eq!((moves.wins, moves.draws, moves.losses), (1, 1, 1));
+        let outcomes = tally(&rules, lines.iter().map(|l| rules.parse_outcomes(l)));
+        assert_eq!((outcomes.wins, outcomes.draws, outcomes.losses), (1, 1, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_permutation_scores() -> Result<()> {
+        let rules = Rules::rock_paper_scissors();
+        assert_eq!(permutations(3).len(), 6);
+        assert_eq!(permutations(3)[0], [0, 1, 2]);
+        let lines = ["A Y", "B X", "C Z"].map(String::from);
+        let (scores, expected) = permutation_scores(&rules, &lines, Interpretation::Moves);
+        assert_eq!(scores.len(), 6);
+        assert_eq!(scores[0], (vec![0, 1, 2], 15));
+        // Each key means every move equally often, so each opponent faces
+        // every move twice over the six assignments
+        assert_eq!(expected, 15.0);
+        let (scores, _) = permutation_scores(&rules, &lines, Interpretation::Outcomes);
+        assert_eq!(scores[0].1, 12);
+        Ok(())
+    }
+
+    #[test]
+    fn test_larger_games() -> Result<()> {
+        let rules = Rules::rock_paper_scissors_lizard_spock();
+        let [rock, spock, paper, lizard, scissors] = [0, 1, 2, 3, 4];
+        assert!(rules.beats(paper, spock));
+        assert!(rules.beats(spock, scissors));
+        assert!(rules.beats(lizard, spock));
+        assert!(rules.beats(rock, lizard));
+        assert!(!rules.beats(scissors, rock));
+        // Every move beats and loses to exactly half of the others
+        for rules in [rules, Rules::rock_paper_scissors_7()] {
+            for myself in 0..rules.move_count() {
+                let wins = (0..rules.move_count())
+                    .filter(|o| rules.beats(myself, *o))
+                    .count();
+                assert_eq!(wins, rules.move_count() / 2);
+            }
+            // The middle key draws, the last key beats the opponent
+            let last = (b'Z') as char;
+            let middle = (b'Z' - rules.move_count() as u8 / 2) as char;
+            let round = rules.parse_outcomes(&format!("A {middle}")).unwrap();
+            assert_eq!(rules.outcome(round.myself, round.opponent), Outcome::Draw);
+            let round = rules.parse_outcomes(&format!("B {last}")).unwrap();
+            assert_eq!(rules.outcome(round.myself, round.opponent), Outcome::Win);
+        }
+        assert!(Rules::cyclic(&["Rock", "Paper"], Scoring::standard(2)).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_part_1_gives_correct_answer() -> Result<()> {
+        let rules = Rules::rock_paper_scissors();
         let lines = common::get_input_lines("day2")?;
-        assert_eq!(total_score(lines.map(first_parser)), 14264);
+        assert_eq!(
+            total_score(&rules, lines.map(|line| rules.parse_moves(&line))),
+            14264
+        );
         Ok(())
     }
 
     #[test]
     fn test_part_2_gives_correct_answer() -> Result<()> {
+        let rules = Rules::rock_paper_scissors();
         let lines = common::get_input_lines("day2")?;
-        assert_eq!(total_score(lines.map(second_parser)), 12382);
+        assert_eq!(
+            total_score(&rules, lines.map(|line| rules.parse_outcomes(&line))),
+            12382
+        );
         Ok(())
     }
 }
@@ -1,5 +1,6 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt};
 
+use crate::days::common::bitset::BitSet64;
 use crate::days::common::{self, Result};
 
 pub fn solution() -> Result<()> {
@@ -7,20 +8,219 @@ pub fn solution() -> Result<()> {
     let lines = common::get_input_lines("day3")?;
     println!("Part 1: {}", part1(lines));
     let lines = common::get_input_lines("day3")?;
-    println!("Part 2: {}", part2(lines));
+    println!("Part 2: {}", part2(lines)?);
     Ok(())
 }
 
 // Input: GwrhJPDJCZFRcwfZWV represents two compartments
 // Length is even number
 // First half represents first compartment, second half represents second
-// Create two sets and take the intersection to find the item in each
-// Then calculate and sum the priorities
+// Priorities run from 1 to 52 so each compartment fits in a 64 bit set of
+// priorities. The intersection then holds the priority of the shared item.
 // a -> 1 A -> 27
 // u8 reps are 97 and 65
 fn part1(lines: impl Iterator<Item = String>) -> u16 {
     lines
         .map(parse_compartments)
+        .map(|(first, second)| {
+            // Ignore bad input
+            (first & second).first().unwrap_or(0) as u16
+        })
+        .sum()
+}
+
+fn parse_compartments(line: String) -> (BitSet64, BitSet64) {
+    let half = line.len() / 2;
+    (
+        to_priority_set(&line.as_bytes()[..half]),
+        to_priority_set(&line.as_bytes()[half..]),
+    )
+}
+
+fn to_priority_set(items: &[u8]) -> BitSet64 {
+    items
+        .iter()
+        .filter(|item| item.is_ascii_alphabetic())
+        .map(|item| to_priority(item) as usize)
+        .collect()
+}
+
+fn to_priority(byte: &u8) -> u16 {
+    if byte.is_ascii_lowercase() {
+        (byte - 96).into()
+    } else {
+        (byte - 38).into()
+    }
+}
+
+// Input: GwrhJPDJCZFRcwfZWV represents a sack
+// Create a priority set out of each sack
+// Chunk the sets in groups of three and take the intersection to find the badge
+fn part2(lines: impl Iterator<Item = String>) -> Result<u16> {
+    sum_badges(lines, 3)
+}
+
+// The sum of badge priorities for groups of any size. Groups without exactly
+// one common item count as 0. Every elf has to be in a full group, so a short
+// group at the end is an error rather than a guess.
+pub fn sum_badges(lines: impl Iterator<Item = String>, group_size: usize) -> Result<u16> {
+    check_group_size(group_size)?;
+    let sets: Vec<BitSet64> = lines.map(|line| to_priority_set(line.as_bytes())).collect();
+    if !sets.len().is_multiple_of(group_size) {
+        return Err(format!(
+            "{} rucksacks don't split into groups of {group_size}.",
+            sets.len()
+        )
+        .into());
+    }
+    Ok(sets
+        .chunks(group_size)
+        .map(common_items)
+        .filter(|common| common.len() == 1)
+        .map(|common| common.first().unwrap_or(0) as u16)
+        .sum())
+}
+
+fn check_group_size(group_size: usize) -> Result<()> {
+    if group_size == 0 {
+        return Err("Groups need at least one rucksack.".into());
+    }
+    Ok(())
+}
+
+// The items every set holds. Empty when there are no sets.
+fn common_items(sets: &[BitSet64]) -> BitSet64 {
+    match sets.split_first() {
+        Some((first, rest)) => rest.iter().fold(*first, |common, set| common & *set),
+        None => BitSet64::new(),
+    }
+}
+
+fn to_item(priority: usize) -> char {
+    if priority <= 26 {
+        (b'a' + priority as u8 - 1) as char
+    } else {
+        (b'A' + priority as u8 - 27) as char
+    }
+}
+
+fn to_items(set: BitSet64) -> Vec<char> {
+    set.iter().map(to_item).collect()
+}
+
+// Something wrong with the input, found by `audit`. Line numbers start at 1.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    OddLength {
+        line: usize,
+        length: usize,
+    },
+    NoSharedItem {
+        line: usize,
+    },
+    SeveralSharedItems {
+        line: usize,
+        items: Vec<char>,
+    },
+    // Also covers a short group at the end of the input
+    NoUniqueBadge {
+        first_line: usize,
+        last_line: usize,
+        candidates: Vec<char>,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::OddLength { line, length } => {
+                write!(f, "Line {line}: odd length {length}")
+            }
+            Problem::NoSharedItem { line } => {
+                write!(f, "Line {line}: no item in both compartments")
+            }
+            Problem::SeveralSharedItems { line, items } => {
+                let items: String = items.iter().collect();
+                write!(
+                    f,
+                    "Line {line}: several items in both compartments '{items}'"
+                )
+            }
+            Problem::NoUniqueBadge {
+                first_line,
+                last_line,
+                candidates,
+            } => {
+                let candidates: String = candidates.iter().collect();
+                write!(
+                    f,
+                    "Lines {first_line}-{last_line}: no unique badge, candidates '{candidates}'"
+                )
+            }
+        }
+    }
+}
+
+// Check every rucksack and every group of `group_size` rucksacks, listing
+// problems in line order with each group after its last rucksack.
+pub fn audit(lines: impl Iterator<Item = String>, group_size: usize) -> Result<Vec<Problem>> {
+    check_group_size(group_size)?;
+    let mut problems = vec![];
+    let mut group = vec![];
+    let mut last_line = 0;
+    for (line_number, line) in (1..).zip(lines) {
+        last_line = line_number;
+        if line.len() % 2 == 1 {
+            problems.push(Problem::OddLength {
+                line: line_number,
+                length: line.len(),
+            });
+        } else {
+            let (first, second) = parse_compartments(line.clone());
+            let shared = first & second;
+            match shared.len() {
+                0 => problems.push(Problem::NoSharedItem { line: line_number }),
+                1 => {}
+                _ => problems.push(Problem::SeveralSharedItems {
+                    line: line_number,
+                    items: to_items(shared),
+                }),
+            }
+        }
+        group.push(to_priority_set(line.as_bytes()));
+        if group.len() == group_size {
+            audit_group(&mut problems, &group, group_size, line_number);
+            group.clear();
+        }
+    }
+    if !group.is_empty() {
+        audit_group(&mut problems, &group, group_size, last_line);
+    }
+    Ok(problems)
+}
+
+fn audit_group(
+    problems: &mut Vec<Problem>,
+    group: &[BitSet64],
+    group_size: usize,
+    last_line: usize,
+) {
+    let common = common_items(group);
+    if common.len() != 1 || group.len() < group_size {
+        problems.push(Problem::NoUniqueBadge {
+            first_line: last_line + 1 - group.len(),
+            last_line,
+            candidates: to_items(common),
+        });
+    }
+}
+
+// The original hash set versions, kept as a reference for the bitset ones.
+
+#[allow(dead_code)]
+fn part1_reference(lines: impl Iterator<Item = String>) -> u16 {
+    lines
+        .map(parse_compartments_reference)
         .map(|(first, second)| {
             if let Some(item) = find_item(&first, &second) {
                 to_priority(&item)
@@ -32,7 +232,7 @@ fn part1(lines: impl Iterator<Item = String>) -> u16 {
         .sum()
 }
 
-fn parse_compartments(line: String) -> (HashSet<u8>, HashSet<u8>) {
+fn parse_compartments_reference(line: String) -> (HashSet<u8>, HashSet<u8>) {
     let half = line.len() / 2;
     let first = line.bytes().take(half).collect();
     let second = line.bytes().skip(half).collect();
@@ -44,19 +244,8 @@ fn find_item(first: &HashSet<u8>, second: &HashSet<u8>) -> Option<u8> {
     first.intersection(second).next().map(|item| *item)
 }
 
-fn to_priority(byte: &u8) -> u16 {
-    if byte.is_ascii_lowercase() {
-        (byte - 96).into()
-    } else {
-        (byte - 38).into()
-    }
-}
-
-// Input: GwrhJPDJCZFRcwfZWV represents a sack
-// Create a set out of each sack
-// Chunk the sets in groups of three and take the intersection to find the badge
-// Then calculate and sum the priorities
-fn part2(lines: impl Iterator<Item = String>) -> u16 {
+#[allow(dead_code)]
+fn part2_reference(lines: impl Iterator<Item = String>) -> u16 {
     lines
         .map(|line| line.bytes().collect::<HashSet<u8>>())
         .collect::<Vec<_>>()
@@ -72,16 +261,13 @@ fn part2(lines: impl Iterator<Item = String>) -> u16 {
         .sum()
 }
 
+// The item common to every set, for a group of any size
 fn find_badge(sets: &[HashSet<u8>]) -> Option<u8> {
-    if let [first, second, third] = &sets[..] {
-        let first_intersection: HashSet<u8> = first.intersection(second).cloned().collect();
-        first_intersection
-            .intersection(third)
-            .next()
-            .map(|item| *item)
-    } else {
-        None
-    }
+    let (first, rest) = sets.split_first()?;
+    let common = rest.iter().fold(first.clone(), |common, set| {
+        common.intersection(set).cloned().collect()
+    });
+    common.into_iter().next()
 }
 
 // Same as other one except uses iterator directly. Unfortunately there seems to
@@ -119,7 +305,64 @@ mod tests {
     #[test]
     fn test_part_2_gives_correct_answer() -> Result<()> {
         let lines = common::get_input_lines("day3")?;
-        assert_eq!(part2(lines), 2708);
+        assert_eq!(part2(lines)?, 2708);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_variants_agree() -> Result<()> {
+        let lines = common::get_input_lines("day3")?;
+        assert_eq!(part1_reference(lines), 8298);
+        let lines = common::get_input_lines("day3")?;
+        assert_eq!(part2_reference(lines), 2708);
+        Ok(())
+    }
+
+    #[test]
+    fn test_badges_for_any_group_size() -> Result<()> {
+        let lines = ["abcX", "dXef", "gXhb", "iXjb"].map(String::from);
+        assert_eq!(sum_badges(lines.clone().into_iter(), 4)?, 50);
+        // The second pair shares both X and b
+        assert_eq!(sum_badges(lines.clone().into_iter(), 2)?, 50);
+        assert_eq!(sum_badges(lines.clone().into_iter(), 1)?, 0);
+        assert_eq!(
+            sum_badges(lines.clone().into_iter(), 3)
+                .unwrap_err()
+                .to_string(),
+            "4 rucksacks don't split into groups of 3."
+        );
+        assert!(sum_badges(lines.clone().into_iter(), 5).is_err());
+        assert!(sum_badges(lines.into_iter(), 0).is_err());
+        assert_eq!(sum_badges(std::iter::empty(), 3)?, 0);
+        let sets: Vec<HashSet<u8>> = ["ab", "bc", "bd", "eb"]
+            .iter()
+            .map(|s| s.bytes().collect())
+            .collect();
+        assert_eq!(find_badge(&sets), Some(b'b'));
+        assert_eq!(find_badge(&[]), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_audit() -> Result<()> {
+        let lines = ["abca", "abc", "abcd", "abab", "xy"].map(String::from);
+        let problems = audit(lines.into_iter(), 2)?;
+        let report: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            report,
+            [
+                "Line 2: odd length 3",
+                "Lines 1-2: no unique badge, candidates 'abc'",
+                "Line 3: no item in both compartments",
+                "Line 4: several items in both compartments 'ab'",
+                "Lines 3-4: no unique badge, candidates 'ab'",
+                "Line 5: no item in both compartments",
+                "Lines 5-5: no unique badge, candidates 'xy'",
+            ]
+        );
+        let lines = common::get_input_lines("day3")?;
+        assert_eq!(audit(lines, 3)?, []);
+        assert!(audit(["ab"].map(String::from).into_iter(), 0).is_err());
         Ok(())
     }
 
@@ -1,3 +1,4 @@
+use crate::days::common::interval::{Interval, RangeSet};
 use crate::days::common::{self, Result};
 
 pub fn solution() -> Result<()> {
@@ -11,26 +12,17 @@ pub fn solution() -> Result<()> {
 
 // Input: 2-4,6-8 represents two ranges: [2, 3, 4] and [6, 7, 8]
 // Need to find if either range is contained in the other
-// Can compare boundaries to check. If the start and end of either range is
-// between the start and end of the other than it is contained.
 // Example:
 //   S . . . E
 //     S . E
 fn part1(lines: impl Iterator<Item = String>) -> u16 {
     parse_ranges(lines)
-        .map(|ranges| {
-            if let Some((first, second)) = ranges {
-                if first.0 >= second.0 && first.1 <= second.1 {
-                    1
-                } else if second.0 >= first.0 && second.1 <= first.1 {
-                    1
-                } else {
-                    0
-                }
-            } else {
-                // Ignoring bad input
-                0
+        .map(|ranges| match ranges {
+            Some((first, second)) => {
+                u16::from(first.contains_interval(&second) || second.contains_interval(&first))
             }
+            // Ignoring bad input
+            None => 0,
         })
         .sum()
 }
@@ -41,46 +33,163 @@ fn part1(lines: impl Iterator<Item = String>) -> u16 {
 //       S . . E
 fn part2(lines: impl Iterator<Item = String>) -> u16 {
     parse_ranges(lines)
-        .map(|ranges| {
-            if let Some((first, second)) = ranges {
-                if first.0 >= second.0 && first.0 <= second.1 {
-                    1
-                } else if first.1 >= second.0 && first.1 <= second.1 {
-                    1
-                } else if second.0 >= first.0 && second.0 <= first.1 {
-                    1
-                } else if second.1 >= first.0 && second.1 <= first.1 {
-                    1
-                } else {
-                    0
-                }
-            } else {
-                // Ignoring bad input
-                0
-            }
+        .map(|ranges| match ranges {
+            Some((first, second)) => u16::from(first.overlaps(&second)),
+            // Ignoring bad input
+            None => 0,
         })
         .sum()
 }
 
-// Parse "2-4,6-8" into ((2,4), (6,8))
+type Sections = Interval<u32>;
+
+// Parse "2-4,6-8" into ([2, 4], [6, 8]). Sections go up to u32::MAX - 1, as
+// an inclusive interval can't end at the maximum.
 fn parse_ranges(
     lines: impl Iterator<Item = String>,
-) -> impl Iterator<Item = Option<((u8, u8), (u8, u8))>> {
+) -> impl Iterator<Item = Option<(Sections, Sections)>> {
     lines.map(|line| {
         let (first, second) = line.split_once(',')?;
         Some((parse_range(first)?, parse_range(second)?))
     })
 }
 
-fn parse_range(range_str: &str) -> Option<(u8, u8)> {
+fn parse_range(range_str: &str) -> Option<Sections> {
     let (start, stop) = range_str.split_once('-')?;
-    Some((start.parse().ok()?, stop.parse().ok()?))
+    let (start, stop) = (start.parse().ok()?, stop.parse().ok()?);
+    (start <= stop).then(|| Interval::inclusive(start, stop))?
+}
+
+// One elf's sections, remembering which line and which half of it they came
+// from. Line numbers start at 1 and elf is 0 or 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Assignment {
+    pub line: usize,
+    pub elf: usize,
+    pub sections: Sections,
+}
+
+// Every assignment in the input as one collection, skipping bad lines
+pub fn parse_assignments(lines: impl Iterator<Item = String>) -> Vec<Assignment> {
+    (1..)
+        .zip(parse_ranges(lines))
+        .filter_map(|(line, ranges)| {
+            let (first, second) = ranges?;
+            Some([(0, first), (1, second)].map(|(elf, sections)| Assignment {
+                line,
+                elf,
+                sections,
+            }))
+        })
+        .flatten()
+        .collect()
+}
+
+// Sections between the lowest and highest assigned that no elf covers
+pub fn uncovered(assignments: &[Assignment]) -> Vec<Sections> {
+    let covered: RangeSet<u32> = assignments.iter().map(|a| a.sections).collect();
+    match (covered.intervals().first(), covered.intervals().last()) {
+        (Some(first), Some(last)) => covered.gaps(&first.hull(last)),
+        _ => vec![],
+    }
+}
+
+// The most elves assigned to any one section. Sweeps over the bounds in order,
+// with ends before starts at the same position since intervals are half open.
+pub fn max_coverage(assignments: &[Assignment]) -> usize {
+    let mut events: Vec<(u32, i32)> = assignments
+        .iter()
+        .flat_map(|a| [(a.sections.start(), 1), (a.sections.end(), -1)])
+        .collect();
+    events.sort_unstable();
+    events
+        .iter()
+        .scan(0, |count, (_, change)| {
+            *count += change;
+            Some(*count as usize)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/*
+Every pair of assignments that overlap, as indexes into the slice with the
+smaller first. Sweeps over assignments by start keeping those still active,
+so each new one overlaps exactly the active ones. Takes O(n log n + pairs)
+rather than comparing all n^2.
+*/
+pub fn overlapping_pairs(assignments: &[Assignment]) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..assignments.len()).collect();
+    order.sort_unstable_by_key(|index| assignments[*index].sections.start());
+    let mut active: Vec<usize> = vec![];
+    let mut pairs = vec![];
+    for index in order {
+        let sections = assignments[index].sections;
+        active.retain(|other| assignments[*other].sections.end() > sections.start());
+        pairs.extend(
+            active
+                .iter()
+                .map(|other| (index.min(*other), index.max(*other))),
+        );
+        active.push(index);
+    }
+    pairs.sort_unstable();
+    pairs
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_range() -> Result<()> {
+        assert_eq!(parse_range("2-4"), Some(Interval::new(2, 5)));
+        assert_eq!(parse_range("250-1000"), Some(Interval::new(250, 1001)));
+        assert_eq!(parse_range("4-2"), None);
+        assert_eq!(parse_range("1-4294967295"), None);
+        assert_eq!(
+            parse_range("1-4294967294"),
+            Interval::inclusive(1, u32::MAX - 1)
+        );
+        assert_eq!(parse_range("a-2"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collection_queries() -> Result<()> {
+        let lines = [
+            "2-4,6-8", "2-3,4-5", "5-7,7-9", "bad", "2-8,3-7", "6-6,4-6", "2-6,4-8",
+        ];
+        let assignments = parse_assignments(lines.iter().map(|l| l.to_string()));
+        assert_eq!(assignments.len(), 12);
+        assert_eq!(
+            assignments[4],
+            Assignment {
+                line: 3,
+                elf: 0,
+                sections: Interval::new(5, 8)
+            }
+        );
+        assert_eq!(uncovered(&assignments), []);
+        assert_eq!(max_coverage(&assignments), 8);
+        let pairs = overlapping_pairs(&assignments);
+        let brute_force: Vec<(usize, usize)> = (0..assignments.len())
+            .flat_map(|i| (i + 1..assignments.len()).map(move |j| (i, j)))
+            .filter(|(i, j)| assignments[*i].sections.overlaps(&assignments[*j].sections))
+            .collect();
+        assert_eq!(pairs, brute_force);
+
+        let lines = ["1-2,10-12", "100000-100001,11-20"];
+        let assignments = parse_assignments(lines.iter().map(|l| l.to_string()));
+        assert_eq!(
+            uncovered(&assignments),
+            [Interval::new(3, 10), Interval::new(21, 100000)]
+        );
+        assert_eq!(max_coverage(&assignments), 2);
+        assert_eq!(overlapping_pairs(&assignments), [(1, 3)]);
+        Ok(())
+    }
+
     #[test]
     fn test_part_1_gives_correct_answer() -> Result<()> {
         let lines = common::get_input_lines("day4")?;
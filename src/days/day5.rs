@@ -1,10 +1,17 @@
-use std::collections::VecDeque;
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt,
+    io::{self, Write},
+    ops::Range,
+};
 
+use crate::days::common::visualize::{Frame, Visualize};
 use crate::days::common::{self, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 
-type Crate = char;
+type Crate = String;
 type Stacks = Vec<VecDeque<Crate>>;
 
 type Amount = usize;
@@ -18,35 +25,157 @@ pub fn solution() -> Result<()> {
 
     let crate_lines = common::get_input_lines(day)?.take_while(|line| line != "");
     let steps_lines = common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
-    let stacks = parse_stacks(crate_lines.collect()).ok_or("Invalid input.")?;
-    let moves = steps_lines.flat_map(parse_step);
-    println!("Part 1: {}", compute_outcome(part1_move_op, stacks, moves));
+    let stacks = parse_stacks(crate_lines.collect())?;
+    let moves = parse_steps(steps_lines)?.into_iter();
+    println!(
+        "Part 1: {}",
+        compute_outcome(&CrateMover9000, stacks, moves)?
+    );
 
     let crate_lines = common::get_input_lines(day)?.take_while(|line| line != "");
     let steps_lines = common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
-    let stacks = parse_stacks(crate_lines.collect()).ok_or("Invalid input.")?;
-    let moves = steps_lines.flat_map(parse_step);
-    println!("Part 2: {}", compute_outcome(part2_move_op, stacks, moves));
+    let stacks = parse_stacks(crate_lines.collect())?;
+    let moves = parse_steps(steps_lines)?.into_iter();
+    println!(
+        "Part 2: {}",
+        compute_outcome(&CrateMover9001, stacks, moves)?
+    );
     Ok(())
 }
 
-// Crates can only be moved one at a time
-fn part1_move_op(mut stacks: Stacks, move_: Move) -> Stacks {
+// Animate the CrateMover 9000 rearrangement, one frame per move
+pub fn visualize(vis: &mut dyn Visualize) -> Result<()> {
+    let day = "day5";
+    let crate_lines = common::get_input_lines(day)?.take_while(|line| !line.is_empty());
+    let steps_lines = common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
+    let mut stacks = parse_stacks(crate_lines.collect())?;
+    vis.emit(&stacks_frame(&stacks))?;
+    for move_ in parse_steps(steps_lines)?.into_iter() {
+        CrateMover9000.apply(&mut stacks, move_)?;
+        vis.emit(&stacks_frame(&stacks))?;
+    }
+    Ok(())
+}
+
+// Draw each stack as a column of crates, bottom crate on the last row. Every
+// column is as wide as the longest label.
+fn stacks_frame(stacks: &Stacks) -> Frame {
+    let height = stacks.iter().map(|stack| stack.len()).max().unwrap_or(0);
+    let label_width = stacks
+        .iter()
+        .flatten()
+        .map(|crate_| crate_.chars().count())
+        .max()
+        .unwrap_or(1);
+    let mut frame = Frame::new(stacks.len() * label_width, height, '.');
+    for (x, stack) in stacks.iter().enumerate() {
+        for (level, crate_) in stack.iter().enumerate() {
+            for (offset, c) in crate_.chars().enumerate() {
+                frame.set(x * label_width + offset, height - level - 1, c);
+            }
+        }
+    }
+    frame
+}
+
+// A crane model, carrying out moves on the stacks. A move that can't be done
+// leaves the stacks as they were.
+pub trait Crane {
+    fn apply(&self, stacks: &mut Stacks, move_: Move) -> std::result::Result<(), MoveError>;
+}
+
+// Why a move can't be done. Stack numbers start at 1 as in the input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MoveError {
+    NoSuchStack { stack: usize },
+    NotEnoughCrates { stack: usize, available: usize },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::NoSuchStack { stack } => write!(f, "there is no stack {stack}"),
+            MoveError::NotEnoughCrates { stack, available } => {
+                write!(f, "stack {stack} only has {available} crates")
+            }
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+fn check_move(stacks: &Stacks, move_: Move) -> std::result::Result<(), MoveError> {
     let (amount, from, to) = move_;
-    for _ in 0..amount {
-        let crate_ = stacks[from].pop_back().unwrap();
-        stacks[to].push_back(crate_);
+    if let Some(stack) = [from, to].into_iter().find(|stack| *stack >= stacks.len()) {
+        return Err(MoveError::NoSuchStack { stack: stack + 1 });
+    }
+    if stacks[from].len() < amount {
+        return Err(MoveError::NotEnoughCrates {
+            stack: from + 1,
+            available: stacks[from].len(),
+        });
+    }
+    Ok(())
+}
+
+// Crates can only be moved one at a time
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn apply(&self, stacks: &mut Stacks, move_: Move) -> std::result::Result<(), MoveError> {
+        check_move(stacks, move_)?;
+        let (amount, from, to) = move_;
+        for _ in 0..amount {
+            if let Some(crate_) = stacks[from].pop_back() {
+                stacks[to].push_back(crate_);
+            }
+        }
+        Ok(())
     }
-    stacks
 }
 
 // Can move N crates at once
-fn part2_move_op(mut stacks: Stacks, move_: Move) -> Stacks {
-    let (amount, from, to) = move_;
-    let index = stacks[from].len() - amount;
-    let mut moved = stacks[from].split_off(index);
-    stacks[to].append(&mut moved);
-    stacks
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn apply(&self, stacks: &mut Stacks, move_: Move) -> std::result::Result<(), MoveError> {
+        check_move(stacks, move_)?;
+        let (amount, from, to) = move_;
+        let index = stacks[from].len() - amount;
+        let mut moved = stacks[from].split_off(index);
+        stacks[to].append(&mut moved);
+        Ok(())
+    }
+}
+
+// Moves at most `capacity` crates at once, keeping the order of each lift. A
+// capacity of 1 acts like a CrateMover 9000 and enough for every move like a
+// 9001.
+pub struct CapacityLimited {
+    capacity: usize,
+}
+
+impl CapacityLimited {
+    pub fn new(capacity: usize) -> Result<Self> {
+        if capacity == 0 {
+            return Err("A crane has to lift at least one crate.".into());
+        }
+        Ok(CapacityLimited { capacity })
+    }
+}
+
+impl Crane for CapacityLimited {
+    fn apply(&self, stacks: &mut Stacks, move_: Move) -> std::result::Result<(), MoveError> {
+        check_move(stacks, move_)?;
+        let (amount, from, to) = move_;
+        let mut remaining = amount;
+        while remaining > 0 {
+            let lift = remaining.min(self.capacity);
+            CrateMover9001.apply(stacks, (lift, from, to))?;
+            remaining -= lift;
+        }
+        Ok(())
+    }
 }
 
 // Parse:
@@ -54,33 +183,100 @@ fn part2_move_op(mut stacks: Stacks, move_: Move) -> Stacks {
 // [N] [C]     -->  [['Z', 'N'], ['M', 'C', 'D'], ['P']]
 // [Z] [M] [P]
 //  1   2   3
-fn parse_stacks(crate_lines: Vec<String>) -> Option<Stacks> {
-    let (index_line, crate_lines) = crate_lines.split_last()?;
-    let width = index_line.chars().next_back()?.to_digit(10)?;
-    let mut stacks = vec![VecDeque::<Crate>::new(); width as usize];
-    for line in crate_lines {
-        for (index, maybe_crate) in parse_crate_line(line).enumerate() {
-            if let Some(crate_) = maybe_crate {
-                stacks[index].push_front(crate_);
+// Each crate belongs to the stack whose number is written under it, so there
+// can be any number of stacks and labels of any width, e.g. [AB] over 10.
+fn parse_stacks(crate_lines: Vec<String>) -> Result<Stacks> {
+    let (index_line, crate_lines) = crate_lines
+        .split_last()
+        .ok_or("Crate drawing has no index line.")?;
+    let columns = parse_index_line(index_line)?;
+    let mut stacks = vec![VecDeque::<Crate>::new(); columns.len()];
+    // Build from the bottom up so a crate with nothing under it is caught
+    for (index, line) in crate_lines.iter().enumerate().rev() {
+        let line_number = index + 1;
+        for (span, crate_) in
+            parse_crate_line(line).map_err(|message| format!("Line {line_number}: {message}"))?
+        {
+            // The stack number has to be inside the brackets
+            let inside = span.start + 1..span.end - 1;
+            let stack = columns
+                .iter()
+                .position(|column| column.start < inside.end && inside.start < column.end)
+                .ok_or(format!(
+                    "Line {line_number}: crate [{crate_}] at column {} is not above any stack.",
+                    span.start + 1
+                ))?;
+            if stacks[stack].len() + line_number != crate_lines.len() {
+                return Err(format!(
+                    "Line {line_number}: crate [{crate_}] in stack {} has nothing under it.",
+                    stack + 1
+                )
+                .into());
             }
+            stacks[stack].push_back(crate_);
         }
     }
-    Some(stacks)
+    Ok(stacks)
 }
 
-// Parse: "    [G] [R]     [P]" into [None, Some('G'), Some('R'), None, Some('P')]
-fn parse_crate_line(line: &String) -> impl Iterator<Item = Option<Crate>> + '_ {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"(\s{4})|(\w{1})").unwrap();
+// Parse " 1   2   3" into the columns each stack number covers, [1..2, 5..6, 9..10].
+// Stacks have to be numbered 1, 2, 3... from left to right.
+fn parse_index_line(line: &str) -> Result<Vec<Range<usize>>> {
+    let columns = tokens(line, |c| c.is_ascii_digit());
+    for (expected, column) in (1..).zip(&columns) {
+        let label: String = line.chars().skip(column.start).take(column.len()).collect();
+        if label != expected.to_string() {
+            return Err(format!(
+                "Index line: stack '{label}' at column {} should be {expected}.",
+                column.start + 1
+            )
+            .into());
+        }
+    }
+    if columns.is_empty() {
+        return Err("Index line: no stacks.".into());
+    }
+    Ok(columns)
+}
+
+// Parse "    [G] [R]     [P]" into [(4..7, "G"), (8..11, "R"), (16..19, "P")],
+// the columns each crate covers including its brackets
+fn parse_crate_line(line: &str) -> Result<Vec<(Range<usize>, Crate)>> {
+    let chars: Vec<char> = line.chars().collect();
+    tokens(line, |c| c != ' ')
+        .into_iter()
+        .map(|span| -> Result<(Range<usize>, Crate)> {
+            let token: String = chars[span.clone()].iter().collect();
+            match token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+                Some(label) if !label.is_empty() && !label.contains(['[', ']']) => {
+                    Ok((span, label.to_string()))
+                }
+                _ => Err(format!("'{token}' at column {} is not a crate.", span.start + 1).into()),
+            }
+        })
+        .collect()
+}
+
+// Columns of each run of characters matching the predicate
+fn tokens(line: &str, predicate: impl Fn(char) -> bool) -> Vec<Range<usize>> {
+    let mut spans = vec![];
+    let mut start = None;
+    for (column, c) in line.chars().chain([' ']).enumerate() {
+        match (start, predicate(c) && c != ' ') {
+            (None, true) => start = Some(column),
+            (Some(first), false) => {
+                spans.push(first..column);
+                start = None;
+            }
+            _ => {}
+        }
     }
-    RE.captures_iter(&line).map(|c| match &c[0] {
-        "    " => None,
-        _ => c[0].chars().next(),
-    })
+    spans
 }
 
 // Parse "move 1 from 2 to 3" into (1, 1, 2). (Converting to zero-index values)
-fn parse_step(move_line: String) -> Option<Move> {
+// Stacks are numbered from 1, so stack 0 is no stack at all.
+fn parse_step(move_line: String) -> Result<Move> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"\d+").unwrap();
     }
@@ -89,36 +285,273 @@ fn parse_step(move_line: String) -> Option<Move> {
         .flat_map(|m| m.as_str().parse())
         .collect::<Vec<usize>>()[..]
     {
-        Some((amount, from - 1, to - 1))
+        let no_stack = MoveError::NoSuchStack { stack: 0 };
+        Ok((
+            amount,
+            from.checked_sub(1).ok_or(no_stack)?,
+            to.checked_sub(1).ok_or(no_stack)?,
+        ))
     } else {
-        None
+        Err(format!("Can't parse '{move_line}' as a move.").into())
+    }
+}
+
+// Every move of a procedure, with the line number of the first bad one
+fn parse_steps(lines: impl Iterator<Item = String>) -> Result<Vec<Move>> {
+    (1..)
+        .zip(lines)
+        .map(|(line_number, line)| {
+            parse_step(line).map_err(|error| format!("Move {line_number}: {error}").into())
+        })
+        .collect()
+}
+
+// The inverse of parse_step, (1, 1, 2) into "move 1 from 2 to 3"
+fn format_step(move_: Move) -> String {
+    let (amount, from, to) = move_;
+    format!("move {amount} from {} to {}", from + 1, to + 1)
+}
+
+// Draw the stacks in the same format parse_stacks reads, index line included
+pub fn format_stacks(stacks: &Stacks) -> String {
+    draw_stacks(stacks, |_, _| false)
+}
+
+/*
+Every stack gets a column wide enough for its longest label or number plus
+the brackets, then a space. The number sits under the first character of the
+label so parse_stacks puts each crate back on the same stack. Crates where
+`highlight(stack, level)` holds are drawn in parentheses instead of brackets.
+*/
+fn draw_stacks(stacks: &Stacks, highlight: impl Fn(usize, usize) -> bool) -> String {
+    let label_width = stacks
+        .iter()
+        .flatten()
+        .map(|crate_| crate_.chars().count())
+        .chain([stacks.len().to_string().len()])
+        .max()
+        .unwrap_or(1);
+    let column_width = label_width + 3;
+    let height = stacks.iter().map(|stack| stack.len()).max().unwrap_or(0);
+    let mut lines: Vec<String> = (0..height)
+        .rev()
+        .map(|level| {
+            let line: String = stacks
+                .iter()
+                .enumerate()
+                .map(|(index, stack)| {
+                    let drawn = match stack.get(level) {
+                        Some(crate_) if highlight(index, level) => format!("({crate_})"),
+                        Some(crate_) => format!("[{crate_}]"),
+                        None => String::new(),
+                    };
+                    format!("{drawn:<column_width$}")
+                })
+                .collect();
+            line.trim_end().to_string()
+        })
+        .collect();
+    let index_line: String = (1..=stacks.len())
+        .map(|number| format!(" {number:<column_width$}", column_width = column_width - 1))
+        .collect();
+    lines.push(index_line.trim_end().to_string());
+    lines.join("\n")
+}
+
+// Write the drawing before the first move and after every move of a
+// compute_outcome run, with the crates just moved in parentheses
+pub fn render_steps(
+    out: &mut impl Write,
+    crane: &(impl Crane + ?Sized),
+    mut stacks: Stacks,
+    moves: impl Iterator<Item = Move>,
+) -> Result<()> {
+    writeln!(out, "{}\n", format_stacks(&stacks))?;
+    for (step, move_) in (1..).zip(moves) {
+        let (amount, _, to) = move_;
+        apply_step(crane, &mut stacks, step, move_)?;
+        let first_moved = stacks[to].len().saturating_sub(amount);
+        let drawing = draw_stacks(&stacks, |stack, level| stack == to && level >= first_moved);
+        writeln!(out, "{}\n{drawing}\n", format_step(move_))?;
+    }
+    Ok(())
+}
+
+// Print every step of the puzzle's procedure, with the CrateMover 9000 for
+// part 1 and the 9001 for part 2
+pub fn print_steps(part: u8) -> Result<()> {
+    let day = "day5";
+    let crate_lines = common::get_input_lines(day)?.take_while(|line| !line.is_empty());
+    let steps_lines = common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
+    let stacks = parse_stacks(crate_lines.collect())?;
+    let moves = parse_steps(steps_lines)?.into_iter();
+    let crane: &dyn Crane = match part {
+        1 => &CrateMover9000,
+        2 => &CrateMover9001,
+        _ => return Err(format!("Day 5 has no part {part}.").into()),
+    };
+    render_steps(&mut io::stdout().lock(), crane, stacks, moves)
+}
+
+// Why a procedure couldn't be finished
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ProcedureError {
+    // The move at `step`, counting from 1, couldn't be done
+    IllegalMove {
+        step: usize,
+        move_: Move,
+        error: MoveError,
+    },
+    // Stacks with no crate on top at the end, numbered from 1
+    EmptyStacks {
+        stacks: Vec<usize>,
+    },
+}
+
+impl fmt::Display for ProcedureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcedureError::IllegalMove { step, move_, error } => {
+                write!(f, "Step {step}, {}: {error}.", format_step(*move_))
+            }
+            ProcedureError::EmptyStacks { stacks } => {
+                let stacks: Vec<String> = stacks.iter().map(|stack| stack.to_string()).collect();
+                write!(f, "Stacks {} are empty.", stacks.join(", "))
+            }
+        }
     }
 }
 
+impl Error for ProcedureError {}
+
+fn apply_step(
+    crane: &(impl Crane + ?Sized),
+    stacks: &mut Stacks,
+    step: usize,
+    move_: Move,
+) -> std::result::Result<(), ProcedureError> {
+    crane
+        .apply(stacks, move_)
+        .map_err(|error| ProcedureError::IllegalMove { step, move_, error })
+}
+
 // Take the initial stacks, apply the moves, then return the string formed from
 // the characters at the top of the stacks
-fn compute_outcome<F>(move_op: F, stacks: Stacks, moves: impl Iterator<Item = Move>) -> String
-where
-    F: Fn(Stacks, Move) -> Stacks,
-{
-    let final_stacks = moves.fold(stacks, |stacks, move_| move_op(stacks, move_));
-    final_stacks
+fn compute_outcome(
+    crane: &(impl Crane + ?Sized),
+    mut stacks: Stacks,
+    moves: impl Iterator<Item = Move>,
+) -> std::result::Result<String, ProcedureError> {
+    for (step, move_) in (1..).zip(moves) {
+        apply_step(crane, &mut stacks, step, move_)?;
+    }
+    let empty: Vec<usize> = (1..)
+        .zip(&stacks)
+        .filter(|(_, stack)| stack.is_empty())
+        .map(|(number, _)| number)
+        .collect();
+    if !empty.is_empty() {
+        return Err(ProcedureError::EmptyStacks { stacks: empty });
+    }
+    Ok(stacks
         .iter()
-        .fold("".to_string(), |mut outcome, stack| {
-            outcome.push(*stack.back().unwrap());
-            outcome
-        })
+        .flat_map(|stack| stack.back())
+        .cloned()
+        .collect())
+}
+
+/*
+Find a shortest list of moves that turns `start` into `target` with the given
+crane, by a breadth first search over arrangements. Every move of any amount
+between two different stacks counts as one step. Gives up with None once
+`limit` arrangements have been seen, or straight away if the two don't hold
+the same crates.
+*/
+pub fn solve(
+    crane: &(impl Crane + ?Sized),
+    start: &Stacks,
+    target: &Stacks,
+    limit: usize,
+) -> Option<Vec<Move>> {
+    let sorted_crates = |stacks: &Stacks| {
+        let mut crates: Vec<Crate> = stacks.iter().flatten().cloned().collect();
+        crates.sort_unstable();
+        crates
+    };
+    if start.len() != target.len() || sorted_crates(start) != sorted_crates(target) {
+        return None;
+    }
+
+    // Each arrangement seen, with the arrangement and move that first reached it
+    let mut seen: HashMap<Stacks, Option<(Stacks, Move)>> = HashMap::from([(start.clone(), None)]);
+    let mut queue = VecDeque::from([start.clone()]);
+    while let Some(stacks) = queue.pop_front() {
+        if &stacks == target {
+            let mut moves = vec![];
+            let mut current = &stacks;
+            while let Some(Some((previous, move_))) = seen.get(current) {
+                moves.push(*move_);
+                current = previous;
+            }
+            moves.reverse();
+            return Some(moves);
+        }
+        for from in 0..stacks.len() {
+            for to in (0..stacks.len()).filter(|to| *to != from) {
+                for amount in 1..=stacks[from].len() {
+                    let mut next = stacks.clone();
+                    if crane.apply(&mut next, (amount, from, to)).is_err()
+                        || seen.contains_key(&next)
+                    {
+                        continue;
+                    }
+                    if seen.len() >= limit {
+                        return None;
+                    }
+                    seen.insert(next.clone(), Some((stacks.clone(), (amount, from, to))));
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    None
+}
+
+// Moves in the text format parse_step reads, one per line
+pub fn format_procedure(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|move_| format_step(*move_))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // Build stacks from strings of single character crates, bottom first
+    fn stacks(columns: &[&str]) -> Stacks {
+        columns
+            .iter()
+            .map(|column| column.chars().map(String::from).collect())
+            .collect()
+    }
+
     #[test]
     fn test_parse_step() -> Result<()> {
+        assert_eq!(parse_step("move 1 from 2 to 3".to_string())?, (1, 1, 2));
+        let error = |line: &str| parse_step(line.to_string()).unwrap_err().to_string();
+        assert_eq!(error("move 1 from 0 to 2"), "there is no stack 0");
+        assert_eq!(error("move 1 from 2 to 0"), "there is no stack 0");
+        assert_eq!(
+            error("move 1 from 2"),
+            "Can't parse 'move 1 from 2' as a move."
+        );
+        let lines = ["move 1 from 2 to 3", "move 2 from 0 to 1"].map(String::from);
         assert_eq!(
-            parse_step("move 1 from 2 to 3".to_string()).unwrap(),
-            (1, 1, 2)
+            parse_steps(lines.into_iter()).unwrap_err().to_string(),
+            "Move 2: there is no stack 0"
         );
         Ok(())
     }
@@ -127,9 +560,15 @@ mod tests {
     fn test_parse_crate_line() -> Result<()> {
         let line = "    [G] [R]         [P]".to_string();
         assert_eq!(
-            parse_crate_line(&line).collect::<Vec<Option<Crate>>>(),
-            vec![None, Some('G'), Some('R'), None, None, Some('P')]
+            parse_crate_line(&line)?,
+            vec![
+                (4..7, "G".to_string()),
+                (8..11, "R".to_string()),
+                (20..23, "P".to_string())
+            ]
         );
+        assert!(parse_crate_line("[A] B").is_err());
+        assert!(parse_crate_line("[]").is_err());
         Ok(())
     }
 
@@ -141,41 +580,259 @@ mod tests {
             "[Z] [M] [P]".to_string(),
             " 1   2   3".to_string(),
         ];
+        assert_eq!(parse_stacks(input)?, stacks(&["ZN", "MCD", "P"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_stacks_wide() -> Result<()> {
+        let mut top = "    ".repeat(10);
+        top.push_str("[K]");
+        let bottom = (0..11)
+            .map(|i| format!("[{}]", (b'A' + i) as char))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let index = (1..=11)
+            .map(|i| format!(" {i:<2}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let parsed = parse_stacks(vec![top, bottom, index])?;
+        assert_eq!(parsed.len(), 11);
+        assert_eq!(
+            parsed[10],
+            VecDeque::from(["K".to_string(), "K".to_string()])
+        );
+
+        let input = ["[AB]       [XYZ]", "[C]  [DE]  [F]", " 1    2     3"];
+        let parsed = parse_stacks(input.map(String::from).to_vec())?;
+        assert_eq!(
+            parsed[0],
+            VecDeque::from(["C".to_string(), "AB".to_string()])
+        );
+        assert_eq!(parsed[1], VecDeque::from(["DE".to_string()]));
         assert_eq!(
-            parse_stacks(input),
-            Some(vec![
-                VecDeque::from(['Z', 'N']),
-                VecDeque::from(['M', 'C', 'D']),
-                VecDeque::from(['P'])
-            ])
+            parsed[2],
+            VecDeque::from(["F".to_string(), "XYZ".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_stacks_errors() -> Result<()> {
+        let parse = |lines: &[&str]| {
+            parse_stacks(lines.iter().map(|l| l.to_string()).collect()).map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            parse(&["[A] [B] [C]", " 1   2"]),
+            Err("Line 1: crate [C] at column 9 is not above any stack.".to_string())
+        );
+        assert_eq!(
+            parse(&["[A]", "    [B]", " 1   2"]),
+            Err("Line 1: crate [A] in stack 1 has nothing under it.".to_string())
+        );
+        assert_eq!(
+            parse(&["[A] [B]", " 1   3"]),
+            Err("Index line: stack '3' at column 6 should be 2.".to_string())
+        );
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["[A]", ""]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_stacks_round_trip() -> Result<()> {
+        let sample = stacks(&["ZN", "MCD", "P"]);
+        let drawing = format_stacks(&sample);
+        assert_eq!(drawing, "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3");
+        assert_eq!(parse_step(format_step((1, 1, 2)).to_string())?, (1, 1, 2));
+
+        let crate_lines: Vec<String> = common::get_input_lines("day5")?
+            .take_while(|line| !line.is_empty())
+            .collect();
+        let parsed = parse_stacks(crate_lines.clone())?;
+        assert_eq!(format_stacks(&parsed), crate_lines.join("\n"));
+
+        let mut wide = stacks(&["", "A", "", "", "", "", "", "", "", "", "B"]);
+        wide[1].push_back("XYZ".to_string());
+        let drawing = format_stacks(&wide);
+        let lines: Vec<String> = drawing.lines().map(String::from).collect();
+        assert_eq!(parse_stacks(lines)?, wide);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_steps() -> Result<()> {
+        let mut out = vec![];
+        let moves = [(1, 1, 0), (3, 0, 2)];
+        render_steps(
+            &mut out,
+            &CrateMover9001,
+            stacks(&["ZN", "MCD", "P"]),
+            moves.into_iter(),
+        )?;
+        let expected = "    [D]\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n\n\
+            move 1 from 2 to 1\n(D)\n[N] [C]\n[Z] [M] [P]\n 1   2   3\n\n\
+            move 3 from 1 to 3\n        (D)\n        (N)\n    [C] (Z)\n    [M] [P]\n 1   2   3\n\n";
+        assert_eq!(String::from_utf8(out)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stacks_frame() -> Result<()> {
+        assert_eq!(
+            stacks_frame(&stacks(&["ZN", "MCD", "P"])).to_string(),
+            ".D.\nNC.\nZMP"
         );
         Ok(())
     }
 
     #[test]
     fn test_compute_outcome() -> Result<()> {
-        let stacks = vec![
-            VecDeque::from(['Z', 'N']),
-            VecDeque::from(['M', 'C', 'D']),
-            VecDeque::from(['P']),
-        ];
+        let stacks = stacks(&["ZN", "MCD", "P"]);
         let steps = vec![(1, 1, 0), (3, 0, 2), (2, 1, 0), (1, 0, 1)];
         assert_eq!(
-            compute_outcome(part1_move_op, stacks, steps.into_iter()),
+            compute_outcome(&CrateMover9000, stacks, steps.into_iter())?,
             "CMZ"
         );
         Ok(())
     }
 
+    #[test]
+    fn test_cranes() -> Result<()> {
+        let moves = [(3, 1, 0), (2, 0, 2)];
+        let outcome = |crane: &dyn Crane| -> Result<Stacks> {
+            let mut stacks = stacks(&["ZN", "MCD", "P"]);
+            for move_ in moves {
+                crane.apply(&mut stacks, move_)?;
+            }
+            Ok(stacks)
+        };
+        assert_eq!(outcome(&CrateMover9000)?, stacks(&["ZND", "", "PMC"]));
+        assert_eq!(outcome(&CrateMover9001)?, stacks(&["ZNM", "", "PCD"]));
+        assert_eq!(
+            outcome(&CapacityLimited::new(1)?)?,
+            outcome(&CrateMover9000)?
+        );
+        assert_eq!(
+            outcome(&CapacityLimited::new(3)?)?,
+            outcome(&CrateMover9001)?
+        );
+        // Lifts C and D then M, then D and M
+        assert_eq!(
+            outcome(&CapacityLimited::new(2)?)?,
+            stacks(&["ZNC", "", "PDM"])
+        );
+        assert!(CapacityLimited::new(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_illegal_moves() -> Result<()> {
+        let mut sample = stacks(&["ZN", "MCD", "P"]);
+        assert_eq!(
+            CrateMover9001.apply(&mut sample, (3, 0, 1)),
+            Err(MoveError::NotEnoughCrates {
+                stack: 1,
+                available: 2
+            })
+        );
+        assert_eq!(
+            CrateMover9000.apply(&mut sample, (1, 0, 3)),
+            Err(MoveError::NoSuchStack { stack: 4 })
+        );
+        assert_eq!(sample, stacks(&["ZN", "MCD", "P"]));
+
+        let error = compute_outcome(
+            &CrateMover9000,
+            sample.clone(),
+            [(1, 2, 0), (2, 2, 1)].into_iter(),
+        );
+        assert_eq!(
+            error.clone().map_err(|e| e.to_string()),
+            Err("Step 2, move 2 from 3 to 2: stack 3 only has 0 crates.".to_string())
+        );
+        assert!(matches!(
+            error,
+            Err(ProcedureError::IllegalMove { step: 2, .. })
+        ));
+        assert_eq!(
+            compute_outcome(&CrateMover9000, sample, [(1, 2, 0)].into_iter()),
+            Err(ProcedureError::EmptyStacks { stacks: vec![3] })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_solve() -> Result<()> {
+        let start = stacks(&["ZN", "MCD", "P"]);
+        let procedure = [(1, 1, 0), (3, 0, 2), (2, 1, 0), (1, 0, 1)];
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let mut target = start.clone();
+            for move_ in procedure {
+                crane.apply(&mut target, move_)?;
+            }
+            let moves = solve(crane, &start, &target, 100_000).ok_or("No solution.")?;
+            assert!(moves.len() <= procedure.len());
+            // Read back in the same way as the puzzle input
+            let text = format_procedure(&moves);
+            let parsed = parse_steps(text.lines().map(String::from))?;
+            assert_eq!(parsed, moves);
+            let mut stacks = start.clone();
+            for move_ in parsed {
+                crane.apply(&mut stacks, move_)?;
+            }
+            assert_eq!(stacks, target);
+        }
+
+        let target = stacks(&["ZND", "MC", "P"]);
+        let moves = solve(&CrateMover9000, &start, &target, 100_000);
+        assert_eq!(moves, Some(vec![(1, 1, 0)]));
+        assert_eq!(solve(&CrateMover9000, &start, &start, 1), Some(vec![]));
+        let target = stacks(&["Z", "MCD", "PN"]);
+        assert_eq!(
+            format_procedure(
+                &solve(&CrateMover9000, &start, &target, 100_000).ok_or("No solution.")?
+            ),
+            "move 1 from 1 to 3"
+        );
+        // Only the 9001 keeps the order of several crates in one move
+        let target = stacks(&["", "MCD", "PZN"]);
+        assert_eq!(
+            solve(&CrateMover9001, &start, &target, 100_000),
+            Some(vec![(2, 0, 2)])
+        );
+        assert_eq!(
+            solve(&CrateMover9000, &start, &target, 100_000).map(|moves| moves.len()),
+            Some(2)
+        );
+        assert_eq!(
+            solve(
+                &CrateMover9000,
+                &start,
+                &stacks(&["ZN", "MCX", "P"]),
+                100_000
+            ),
+            None
+        );
+        assert_eq!(
+            solve(&CrateMover9000, &start, &stacks(&["", "", "PZNMCD"]), 10),
+            None
+        );
+        Ok(())
+    }
 
     #[test]
     fn test_part_one() -> Result<()> {
         let day = "day5";
         let crate_lines = common::get_input_lines(day)?.take_while(|line| line != "");
-        let steps_lines = common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
-        let stacks = parse_stacks(crate_lines.collect()).ok_or("Invalid input.")?;
-        let moves = steps_lines.flat_map(parse_step);
-        assert_eq!(compute_outcome(part1_move_op, stacks, moves), "VCTFTJQCG");
+        let steps_lines =
+            common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
+        let stacks = parse_stacks(crate_lines.collect())?;
+        let moves = parse_steps(steps_lines)?.into_iter();
+        assert_eq!(
+            compute_outcome(&CrateMover9000, stacks, moves)?,
+            "VCTFTJQCG"
+        );
         Ok(())
     }
 
@@ -183,12 +840,14 @@ mod tests {
     fn test_part_two() -> Result<()> {
         let day = "day5";
         let crate_lines = common::get_input_lines(day)?.take_while(|line| line != "");
-        let steps_lines = common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
-        let stacks = parse_stacks(crate_lines.collect()).ok_or("Invalid input.")?;
-        let moves = steps_lines.flat_map(parse_step);
-        assert_eq!(compute_outcome(part2_move_op, stacks, moves), "GCFGLDNJZ");
+        let steps_lines =
+            common::get_input_lines(day)?.skip_while(|line| !line.starts_with("move"));
+        let stacks = parse_stacks(crate_lines.collect())?;
+        let moves = parse_steps(steps_lines)?.into_iter();
+        assert_eq!(
+            compute_outcome(&CrateMover9001, stacks, moves)?,
+            "GCFGLDNJZ"
+        );
         Ok(())
     }
-
-
 }
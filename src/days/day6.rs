@@ -1,22 +1,387 @@
+use crate::days::common::bitset::BitSet128;
 use crate::days::common::Result;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    fs,
+    fmt,
+    fs::File,
+    io::{self, Read},
+    mem,
 };
 // extern crate test;
 
 pub fn solution() -> Result<()> {
     println!("~~~~~~~~~~~~~ Day 6 ~~~~~~~~~~~~~");
 
-    let input = fs::read_to_string(format!("./input/day6.txt"))?;
-    println!("Part 1: {}", find_first_marker(&input, 4)?);
-    println!("Part 2: {}", find_first_marker(&input, 14)?);
+    let file = File::open("./input/day6.txt")?;
+    let [packet, message] = first_markers(file, &[4, 14])?[..] else {
+        unreachable!()
+    };
+    println!("Part 1: {}", packet.ok_or("No marker found.")?);
+    println!("Part 2: {}", message.ok_or("No marker found.")?);
+    Ok(())
+}
+
+// The last `window_size` bytes before `position` are all different
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Marker {
+    pub window_size: usize,
+    pub position: usize,
+}
+
+const CHUNK_SIZE: usize = 8192;
+
+// Reads a stream a chunk at a time, handing out single bytes
+struct ChunkReader<R> {
+    reader: R,
+    chunk: Box<[u8; CHUNK_SIZE]>,
+    filled: usize,
+    cursor: usize,
+}
+
+impl<R: Read> ChunkReader<R> {
+    fn new(reader: R) -> Self {
+        ChunkReader {
+            reader,
+            chunk: Box::new([0; CHUNK_SIZE]),
+            filled: 0,
+            cursor: 0,
+        }
+    }
+
+    // The next byte, reading another chunk when this one runs out
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.cursor == self.filled {
+            self.filled = loop {
+                match self.reader.read(&mut self.chunk[..]) {
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    result => break result?,
+                }
+            };
+            self.cursor = 0;
+            if self.filled == 0 {
+                return Ok(None);
+            }
+        }
+        self.cursor += 1;
+        Ok(Some(self.chunk[self.cursor - 1]))
+    }
+}
+
+// Remembers where each byte was last seen, which gives the longest run of
+// distinct bytes ending at the latest one in O(1)
+struct DistinctRun {
+    // One past the position each byte was last seen at, 0 if never
+    last_seen: [usize; 256],
+    run_start: usize,
+    position: usize,
+}
+
+impl DistinctRun {
+    fn new() -> Self {
+        DistinctRun {
+            last_seen: [0; 256],
+            run_start: 0,
+            position: 0,
+        }
+    }
+
+    // Add a byte and return the length of the run it ends
+    fn push(&mut self, byte: u8) -> usize {
+        self.run_start = self.run_start.max(self.last_seen[byte as usize]);
+        self.position += 1;
+        self.last_seen[byte as usize] = self.position;
+        self.position - self.run_start
+    }
+}
+
+/*
+Finds markers of several sizes in one pass over any reader, holding only one
+chunk of the stream at a time. Every window size up to the length of the
+distinct run at a position is a marker there, so the cost doesn't grow with
+the number of window sizes. Works for all 256 byte values. Markers come in
+order of position, then window size.
+*/
+pub struct MarkerDetector<R> {
+    reader: ChunkReader<R>,
+    window_sizes: Vec<usize>,
+    run: DistinctRun,
+    // Window sizes still to report at the current position
+    pending: std::ops::Range<usize>,
+    failed: bool,
+}
+
+impl<R: Read> MarkerDetector<R> {
+    pub fn new(reader: R, window_sizes: &[usize]) -> Self {
+        let mut window_sizes: Vec<usize> = window_sizes
+            .iter()
+            .copied()
+            .filter(|size| *size > 0)
+            .collect();
+        window_sizes.sort_unstable();
+        window_sizes.dedup();
+        MarkerDetector {
+            reader: ChunkReader::new(reader),
+            window_sizes,
+            run: DistinctRun::new(),
+            pending: 0..0,
+            failed: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for MarkerDetector<R> {
+    type Item = io::Result<Marker>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if self.failed {
+                return None;
+            }
+            let byte = match self.reader.next_byte() {
+                Ok(Some(byte)) => byte,
+                Ok(None) => return None,
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(error));
+                }
+            };
+            let run = self.run.push(byte);
+            self.pending = 0..self.window_sizes.partition_point(|size| *size <= run);
+        }
+        let index = self.pending.next()?;
+        Some(Ok(Marker {
+            window_size: self.window_sizes[index],
+            position: self.run.position,
+        }))
+    }
+}
+
+// The first marker position for each window size, in the order given.
+// Stops reading as soon as every size has been found.
+pub fn first_markers(reader: impl Read, window_sizes: &[usize]) -> Result<Vec<Option<usize>>> {
+    let mut firsts = vec![None; window_sizes.len()];
+    for marker in MarkerDetector::new(reader, window_sizes) {
+        let marker = marker?;
+        for (size, first) in window_sizes.iter().zip(firsts.iter_mut()) {
+            if *size == marker.window_size && first.is_none() {
+                *first = Some(marker.position);
+            }
+        }
+        if firsts.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    Ok(firsts)
+}
+
+// Slide a window over the stream, toggling each byte in a bitset as it enters
+// and leaves. A repeated byte cancels itself out in pairs, so the popcount
+// only equals the window size when every byte in the window is distinct. The
+// in-memory version, kept as a reference for the streaming one.
+#[allow(dead_code)]
+fn find_first_marker(input: &str, window_size: usize) -> Result<usize> {
+    let bytes = input.as_bytes();
+    if !bytes.is_ascii() {
+        return Err("Datastream is not ASCII.".into());
+    }
+    let mut window = BitSet128::new();
+    for (index, byte) in bytes.iter().enumerate() {
+        window.toggle(*byte as usize);
+        if index >= window_size {
+            window.toggle(bytes[index - window_size] as usize);
+        }
+        if window.len() == window_size {
+            return Ok(index + 1);
+        }
+    }
+    Err("No marker found.".into())
+}
+
+pub const PACKET_MARKER: usize = 4;
+pub const MESSAGE_MARKER: usize = 14;
+
+// A marker and the payload up to the next one. Offsets count bytes from the
+// start of the stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub offset: usize,
+    pub marker: Vec<u8>,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn payload_offset(&self) -> usize {
+        self.offset + self.marker.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.marker.len() + self.payload.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// A header line, then the marker and payload as hexdump lines of 16 bytes
+// with their offsets, e.g.
+// frame 0x0000000b, marker 4 bytes, payload 3 bytes
+// 0000000b  6a 70 71 6d                                      |jpqm|
+// 0000000f  67 62 6c                                         |gbl|
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "frame {:#010x}, marker {} bytes, payload {} bytes",
+            self.offset,
+            self.marker.len(),
+            self.payload.len()
+        )?;
+        let parts = [
+            (self.offset, &self.marker),
+            (self.payload_offset(), &self.payload),
+        ];
+        for (offset, bytes) in parts {
+            for (line, chunk) in bytes.chunks(16).enumerate() {
+                let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+                let text: String = chunk
+                    .iter()
+                    .map(|byte| {
+                        if byte.is_ascii_graphic() || *byte == b' ' {
+                            *byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "\n{:08x}  {:<47}  |{text}|",
+                    offset + line * 16,
+                    hex.join(" ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/*
+Splits a stream into frames, each starting at a marker of `window_size`
+distinct bytes and running up to the next marker that doesn't overlap it.
+Bytes before the first marker aren't part of any frame. Only the frame being
+built is held in memory, along with one chunk of the stream.
+*/
+pub struct FrameDecoder<R> {
+    reader: ChunkReader<R>,
+    window_size: usize,
+    run: DistinctRun,
+    // The frame being built, or the last few bytes before the first marker
+    current: Option<Frame>,
+    preamble: VecDeque<u8>,
+    failed: bool,
+}
+
+impl<R: Read> FrameDecoder<R> {
+    pub fn new(reader: R, window_size: usize) -> Self {
+        FrameDecoder {
+            reader: ChunkReader::new(reader),
+            window_size: window_size.max(1),
+            run: DistinctRun::new(),
+            current: None,
+            preamble: VecDeque::new(),
+            failed: false,
+        }
+    }
+
+    pub fn packets(reader: R) -> Self {
+        FrameDecoder::new(reader, PACKET_MARKER)
+    }
+
+    pub fn messages(reader: R) -> Self {
+        FrameDecoder::new(reader, MESSAGE_MARKER)
+    }
+
+    // Start a new frame if the byte ends a marker, returning the finished one
+    fn push(&mut self, byte: u8) -> Option<Frame> {
+        let run = self.run.push(byte);
+        let start = self.run.position.saturating_sub(self.window_size);
+        let is_marker = run >= self.window_size
+            && self
+                .current
+                .as_ref()
+                .is_none_or(|frame| start >= frame.payload_offset());
+        match &mut self.current {
+            Some(frame) if !is_marker => {
+                frame.payload.push(byte);
+                None
+            }
+            None if !is_marker => {
+                self.preamble.push_back(byte);
+                if self.preamble.len() >= self.window_size {
+                    self.preamble.pop_front();
+                }
+                None
+            }
+            _ => {
+                // The marker's bytes so far are at the end of the previous
+                // frame's payload, or of the preamble
+                let mut marker = match &mut self.current {
+                    Some(frame) => frame
+                        .payload
+                        .split_off(frame.payload.len() + 1 - self.window_size),
+                    None => mem::take(&mut self.preamble).into(),
+                };
+                marker.push(byte);
+                let frame = Frame {
+                    offset: start,
+                    marker,
+                    payload: vec![],
+                };
+                self.current.replace(frame)
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for FrameDecoder<R> {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            match self.reader.next_byte() {
+                Ok(Some(byte)) => {
+                    if let Some(frame) = self.push(byte) {
+                        return Some(Ok(frame));
+                    }
+                }
+                Ok(None) => return self.current.take().map(Ok),
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        None
+    }
+}
+
+// Print every packet and message frame of the puzzle input
+pub fn report() -> Result<()> {
+    for (name, window_size) in [("Packets", PACKET_MARKER), ("Messages", MESSAGE_MARKER)] {
+        println!("{name}");
+        let file = File::open("./input/day6.txt")?;
+        for frame in FrameDecoder::new(file, window_size) {
+            println!("{}", frame?);
+        }
+    }
     Ok(())
 }
 
 // Iterate through sliding windows and simply check if the window contains
-// distinct values using a hash set.
-fn find_first_marker(input: &String, window_size: usize) -> Result<usize> {
+// distinct values using a hash set. The original version, kept as a reference.
+#[allow(dead_code)]
+fn find_first_marker_hash_set(input: &String, window_size: usize) -> Result<usize> {
     for (index, buf) in input.as_bytes().windows(window_size).enumerate() {
         if buf.iter().collect::<HashSet<&u8>>().len() == window_size {
             return Ok(index + window_size);
@@ -29,7 +394,7 @@ fn find_first_marker(input: &String, window_size: usize) -> Result<usize> {
 // count of each item in the queue. When the count goes to zero, remove the item
 // from the hash map. If the length of the hash map matches the length of the
 // queue, then all items in the queue must be distinct.
-fn find_first_marker_optimized(input: &String, window_size: usize) -> Result<usize> {
+pub fn find_first_marker_optimized(input: &String, window_size: usize) -> Result<usize> {
     let mut unique = HashMap::<u8, usize>::new();
     for byte in input.bytes().take(window_size) {
         unique.entry(byte).and_modify(|c| *c += 1).or_insert(1);
@@ -58,6 +423,8 @@ fn find_first_marker_optimized(input: &String, window_size: usize) -> Result<usi
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::days::common::random::Lcg;
+    use std::fs;
     // use test::Bencher;
 
     #[test]
@@ -74,6 +441,176 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_variants_agree() -> Result<()> {
+        let input = fs::read_to_string("./input/day6.txt")?;
+        let samples = [
+            "mjqjpqmgbljsphdztnvjfqwrcgsmlb".to_string(),
+            "bvwbjplbgvbhsrlpgdmjqwftvncz".to_string(),
+            "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg".to_string(),
+            input,
+        ];
+        for sample in &samples {
+            for window_size in [4, 14] {
+                let expected = find_first_marker_hash_set(sample, window_size)?;
+                assert_eq!(find_first_marker(sample, window_size)?, expected);
+                assert_eq!(find_first_marker_optimized(sample, window_size)?, expected);
+            }
+        }
+        assert!(find_first_marker("aaaa", 4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_marker_detector() -> Result<()> {
+        let sample = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+        let markers: Vec<Marker> =
+            MarkerDetector::new(sample.as_bytes(), &[4, 14]).collect::<io::Result<_>>()?;
+        let positions = |size| {
+            markers
+                .iter()
+                .filter(|marker| marker.window_size == size)
+                .map(|marker| marker.position)
+                .collect::<Vec<_>>()
+        };
+        // Every window of 4 distinct bytes, not only the first
+        let expected: Vec<usize> = sample
+            .as_bytes()
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| window.iter().collect::<HashSet<_>>().len() == 4)
+            .map(|(index, _)| index + 4)
+            .collect();
+        assert_eq!(positions(4), expected);
+        assert_eq!(positions(14).first(), Some(&19));
+        assert!(markers
+            .windows(2)
+            .all(|pair| pair[0].position <= pair[1].position));
+
+        let file = File::open("./input/day6.txt")?;
+        assert_eq!(
+            first_markers(file, &[14, 4, 50])?,
+            [Some(2145), Some(1544), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_marker_detector_any_bytes() -> Result<()> {
+        // Binary data over several chunks, with a marker straddling two of them
+        let mut stream = vec![0u8; CHUNK_SIZE - 100];
+        stream.extend((0..=255).rev());
+        stream.extend(vec![7; CHUNK_SIZE]);
+        let first = first_markers(stream.as_slice(), &[2, 200, 256, 257])?;
+        assert_eq!(
+            first,
+            [
+                Some(CHUNK_SIZE - 99),
+                // The last 0 before the descending run is part of it
+                Some(CHUNK_SIZE + 99),
+                Some(CHUNK_SIZE + 155),
+                None
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_marker_detector_matches_reference() -> Result<()> {
+        // The 14 byte marker ends on the first byte of the second chunk
+        let mut stream: String = "abcdefghijklm".chars().cycle().take(CHUNK_SIZE).collect();
+        stream.push_str("nopqrstuvwxyz");
+        let sizes = [4, 13, 14];
+        let expected: Vec<Option<usize>> = sizes
+            .iter()
+            .map(|size| find_first_marker_optimized(&stream, *size).ok())
+            .collect();
+        assert_eq!(expected[2], Some(CHUNK_SIZE + 1));
+        assert_eq!(first_markers(stream.as_bytes(), &sizes)?, expected);
+
+        // Pseudo random letters over a few chunks, where every marker of every
+        // size has to match the windows that are all distinct
+        let mut lcg = Lcg::new(6);
+        let stream: String = (0..3 * CHUNK_SIZE + 500)
+            .map(|_| char::from(b'a' + lcg.below(16) as u8))
+            .collect();
+        let sizes = [4, 8, 11, 12];
+        let markers: Vec<Marker> =
+            MarkerDetector::new(stream.as_bytes(), &sizes).collect::<io::Result<_>>()?;
+        for size in sizes {
+            let expected: Vec<usize> = stream
+                .as_bytes()
+                .windows(size)
+                .enumerate()
+                .filter(|(_, window)| window.iter().collect::<HashSet<_>>().len() == size)
+                .map(|(index, _)| index + size)
+                .collect();
+            let found: Vec<usize> = markers
+                .iter()
+                .filter(|marker| marker.window_size == size)
+                .map(|marker| marker.position)
+                .collect();
+            assert_eq!(found, expected);
+            assert_eq!(
+                first_markers(stream.as_bytes(), &[size])?,
+                [find_first_marker_optimized(&stream, size).ok()]
+            );
+        }
+        assert!(markers
+            .iter()
+            .any(|marker| marker.position > 2 * CHUNK_SIZE));
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_decoder() -> Result<()> {
+        let stream = "aaabcdaaaaabcdxxxx";
+        let frames: Vec<Frame> =
+            FrameDecoder::packets(stream.as_bytes()).collect::<io::Result<_>>()?;
+        assert_eq!(
+            frames,
+            [
+                Frame {
+                    offset: 2,
+                    marker: b"abcd".to_vec(),
+                    payload: b"aaaa".to_vec()
+                },
+                Frame {
+                    offset: 10,
+                    marker: b"abcd".to_vec(),
+                    payload: b"xxxx".to_vec()
+                },
+            ]
+        );
+        assert_eq!(frames[1].payload_offset(), 14);
+        assert_eq!(frames[1].len(), 8);
+        assert_eq!(
+            frames[0].to_string(),
+            "frame 0x00000002, marker 4 bytes, payload 4 bytes\n\
+             00000002  61 62 63 64                                      |abcd|\n\
+             00000006  61 61 61 61                                      |aaaa|"
+        );
+        assert!(FrameDecoder::packets("aaaa".as_bytes()).next().is_none());
+
+        // Frames cover the stream from the first marker on, and each one
+        // starts where its window first has no repeats
+        let input = fs::read_to_string("./input/day6.txt")?;
+        let frames: Vec<Frame> =
+            FrameDecoder::messages(input.as_bytes()).collect::<io::Result<_>>()?;
+        assert_eq!(frames[0].payload_offset(), 2145);
+        let joined: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| frame.marker.iter().chain(&frame.payload))
+            .copied()
+            .collect();
+        assert_eq!(joined, input.as_bytes()[frames[0].offset..]);
+        for frame in &frames {
+            let marker: HashSet<&u8> = frame.marker.iter().collect();
+            assert_eq!(marker.len(), MESSAGE_MARKER);
+        }
+        Ok(())
+    }
+
     // #[bench]
     // fn bench_find_first_marker(b: &mut Bencher) -> Result<()> {
     //     let input = fs::read_to_string(format!("./input/day6.txt"))?;
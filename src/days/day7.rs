@@ -1,85 +1,140 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    path::Path,
+    str::FromStr,
+};
 
 use crate::days::common::{self, Result};
 
 pub fn solution() -> Result<()> {
     println!("~~~~~~~~~~~~~ Day 7 ~~~~~~~~~~~~~");
-    println!("Part 1: {}", part1(common::get_input_lines("day7")?));
+    println!("Part 1: {}", part1(common::get_input_lines("day7")?)?);
     println!(
         "Part 2: {}",
-        part2(common::get_input_lines("day7")?).ok_or("No dirs are big enough.")?
+        part2(common::get_input_lines("day7")?)?.ok_or("No dirs are big enough.")?
     );
     Ok(())
 }
 
-fn part1(lines: impl Iterator<Item = String>) -> u32 {
-    get_sizes(lines)
+fn part1(lines: impl Iterator<Item = String>) -> Result<u64> {
+    Ok(FileSystem::from_log(lines)?
+        .du()
         .into_iter()
         .map(|(_, size)| size)
         .filter(|size| *size <= 100_000)
-        .sum()
+        .sum())
 }
 
-fn part2(lines: impl Iterator<Item = String>) -> Option<u32> {
-    let dir_sizes = get_sizes(lines);
-    let unused = 70_000_000 - dir_sizes.get("/").unwrap();
-    let space_needed = 30_000_000 - unused;
-    let mut sizes_vec = dir_sizes
-        .into_iter()
-        .map(|(_, size)| size)
-        .collect::<Vec<u32>>();
-    sizes_vec.sort();
-    for size in sizes_vec {
-        if size > space_needed {
-            return Some(size);
-        }
-    }
-    None
-}
-
-fn get_sizes(lines: impl Iterator<Item = String>) -> HashMap<String, u32> {
-    let (_, dir_sizes) = lines.flat_map(parse_line).fold(
-        (Vec::<String>::new(), HashMap::<String, u32>::new()),
-        |(mut stack, mut dir_sizes), log| match log {
-            Log::Command(command) => match command {
-                Command::Cd(dir_name) => match dir_name.as_str() {
-                    ".." => {
-                        stack.pop();
-                        (stack, dir_sizes)
-                    }
-                    "/" => {
-                        stack.push(dir_name);
-                        (stack, dir_sizes)
-                    }
-                    _ => {
-                        let mut qualified = stack.join("");
-                        qualified.push_str(&dir_name);
-                        stack.push(qualified);
-                        (stack, dir_sizes)
-                    }
-                },
-                Command::Ls => (stack, dir_sizes),
+const DISK_CAPACITY: u64 = 70_000_000;
+const SPACE_NEEDED: u64 = 30_000_000;
+
+// None when no directory frees enough space. A bad log is an error.
+fn part2(lines: impl Iterator<Item = String>) -> Result<Option<u64>> {
+    let fs = FileSystem::from_log(lines)?;
+    Ok(fs
+        .smallest_directory(DISK_CAPACITY, SPACE_NEEDED)
+        .map(|plan| plan.freed))
+}
+
+// The total size of every directory, found by adding each file's size to the
+// directory it is listed in and all of that directory's ancestors. Listing a
+// directory twice counts its files twice. The original flat version, kept as
+// a reference for the tree.
+#[allow(dead_code)]
+fn get_sizes(lines: impl Iterator<Item = String>) -> HashMap<FsPath, u64> {
+    let mut cwd = FsPath::root();
+    let mut dir_sizes = HashMap::<FsPath, u64>::new();
+    for log in lines.flat_map(parse_line) {
+        match log {
+            Log::Command(Command::Cd(dir_name)) => match dir_name.as_str() {
+                ".." => cwd = cwd.parent().unwrap_or_default(),
+                "/" => cwd = FsPath::root(),
+                _ => cwd = cwd.join(&dir_name),
             },
-            Log::Dir(_) => (stack, dir_sizes),
-            Log::File(size) => {
-                stack.iter().for_each(|dir_name| {
-                    dir_sizes
-                        .entry(dir_name.to_string())
-                        .and_modify(|c| *c += size)
-                        .or_insert(size);
-                });
-                (stack, dir_sizes)
+            Log::Command(Command::Ls) => {}
+            Log::Dir(_) => {}
+            Log::File(size, _) => {
+                for dir in cwd.ancestors() {
+                    *dir_sizes.entry(dir).or_insert(0) += size;
+                }
             }
-        },
-    );
+        }
+    }
     dir_sizes
 }
 
+// A path from the root as its list of names, so "/a" then "bc" and "/ab" then
+// "c" stay different paths. Displays with "/" separators, the root as "/".
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FsPath {
+    names: Vec<String>,
+}
+
+impl FsPath {
+    pub fn root() -> Self {
+        FsPath::default()
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn join(&self, name: &str) -> Self {
+        let mut names = self.names.clone();
+        names.push(name.to_string());
+        FsPath { names }
+    }
+
+    // None for the root
+    pub fn parent(&self) -> Option<Self> {
+        let (_, names) = self.names.split_last()?;
+        Some(FsPath {
+            names: names.to_vec(),
+        })
+    }
+
+    // This path, then its parent and so on up to the root
+    pub fn ancestors(&self) -> impl Iterator<Item = FsPath> + '_ {
+        (0..=self.names.len()).rev().map(|length| FsPath {
+            names: self.names[..length].to_vec(),
+        })
+    }
+}
+
+impl fmt::Display for FsPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.names.join("/"))
+    }
+}
+
+// Parse "/a/e" or "/a/e/" into [a, e]. Paths have to start at the root.
+impl FromStr for FsPath {
+    type Err = String;
+
+    fn from_str(path: &str) -> std::result::Result<Self, Self::Err> {
+        let names = path
+            .strip_prefix('/')
+            .ok_or(format!("'{path}' doesn't start at the root."))?;
+        Ok(FsPath {
+            names: names
+                .split('/')
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+}
+
 #[derive(PartialEq, Debug)]
 enum Log {
     Command(Command),
     Dir(String),
-    File(u32),
+    File(u64, String),
 }
 
 #[derive(PartialEq, Debug)]
@@ -97,7 +152,529 @@ fn parse_line(line: String) -> Option<Log> {
             _ => None,
         },
         "dir" => Some(Log::Dir(parts.next()?.to_string())),
-        first_part @ _ => Some(Log::File(first_part.parse().ok()?)),
+        first_part @ _ => Some(Log::File(
+            first_part.parse().ok()?,
+            parts.next()?.to_string(),
+        )),
+    }
+}
+
+const ROOT: usize = 0;
+
+#[derive(Debug)]
+enum Node {
+    Dir(BTreeMap<String, usize>),
+    File(u64),
+}
+
+/*
+The directory tree described by a terminal log. Nodes live in a vector and
+refer to each other by index, the root being 0. Every node is created after
+its parent, so walking the vector backwards visits children before parents.
+*/
+#[derive(Debug)]
+pub struct FileSystem {
+    names: Vec<String>,
+    parents: Vec<usize>,
+    nodes: Vec<Node>,
+}
+
+// A file or directory found by walking the tree. Directory sizes include
+// everything below them.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub path: FsPath,
+    pub name: String,
+    pub depth: usize,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+impl FileSystem {
+    fn empty() -> FileSystem {
+        FileSystem {
+            names: vec!["/".to_string()],
+            parents: vec![ROOT],
+            nodes: vec![Node::Dir(BTreeMap::new())],
+        }
+    }
+
+    /*
+    Replay the log. `cd /` goes to the root from anywhere, `cd ..` at the root
+    stays there and `cd` into a directory that was never listed creates it.
+    Listing a directory again adds anything new and updates file sizes. A name
+    listed as both a file and a directory is an error.
+    */
+    pub fn from_log(lines: impl Iterator<Item = String>) -> Result<FileSystem> {
+        let mut fs = FileSystem::empty();
+        let mut cwd = ROOT;
+        for (line_number, line) in (1..).zip(lines) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let log = parse_line(line.clone())
+                .ok_or(format!("Line {line_number}: can't parse '{line}'."))?;
+            let result = match log {
+                Log::Command(Command::Cd(name)) => match name.as_str() {
+                    "/" => Ok(ROOT),
+                    ".." => Ok(fs.parents[cwd]),
+                    _ => fs.child(cwd, &name, None),
+                }
+                .map(|dir| cwd = dir),
+                Log::Command(Command::Ls) => Ok(()),
+                Log::Dir(name) => fs.child(cwd, &name, None).map(|_| ()),
+                Log::File(size, name) => fs.child(cwd, &name, Some(size)).map(|_| ()),
+            };
+            result.map_err(|message| format!("Line {line_number}: {message}"))?;
+        }
+        Ok(fs)
+    }
+
+    // Find or create the named directory, or file with a size, in `dir`
+    fn child(&mut self, dir: usize, name: &str, size: Option<u64>) -> Result<usize> {
+        let next = self.nodes.len();
+        let Node::Dir(children) = &mut self.nodes[dir] else {
+            unreachable!("only directories are ever current");
+        };
+        let index = *children.entry(name.to_string()).or_insert(next);
+        if index == next {
+            self.names.push(name.to_string());
+            self.parents.push(dir);
+            self.nodes.push(match size {
+                Some(size) => Node::File(size),
+                None => Node::Dir(BTreeMap::new()),
+            });
+        }
+        match (&mut self.nodes[index], size) {
+            (Node::File(old), Some(size)) => *old = size,
+            (Node::Dir(_), None) => {}
+            _ => return Err(format!("'{name}' is both a file and a directory.").into()),
+        }
+        Ok(index)
+    }
+
+    // The size of every node, with directories totalled
+    fn totals(&self) -> Vec<u64> {
+        let mut totals: Vec<u64> = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::File(size) => *size,
+                Node::Dir(_) => 0,
+            })
+            .collect();
+        for index in (1..self.nodes.len()).rev() {
+            totals[self.parents[index]] += totals[index];
+        }
+        totals
+    }
+
+    fn path(&self, mut index: usize) -> FsPath {
+        let mut names = vec![];
+        while index != ROOT {
+            names.push(self.names[index].clone());
+            index = self.parents[index];
+        }
+        names.reverse();
+        FsPath { names }
+    }
+
+    fn lookup(&self, path: &FsPath) -> Option<usize> {
+        path.names()
+            .iter()
+            .try_fold(ROOT, |index, name| match &self.nodes[index] {
+                Node::Dir(children) => children.get(name).copied(),
+                Node::File(_) => None,
+            })
+    }
+
+    // Total size of the file or directory at the path
+    pub fn size(&self, path: &FsPath) -> Option<u64> {
+        self.lookup(path).map(|index| self.totals()[index])
+    }
+
+    // Every file and directory, each directory followed by its contents in
+    // name order
+    pub fn entries(&self) -> Vec<Entry> {
+        let totals = self.totals();
+        let mut entries = vec![];
+        let mut stack = vec![(ROOT, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            let is_dir = match &self.nodes[index] {
+                Node::Dir(children) => {
+                    stack.extend(children.values().rev().map(|child| (*child, depth + 1)));
+                    true
+                }
+                Node::File(_) => false,
+            };
+            entries.push(Entry {
+                path: self.path(index),
+                name: self.names[index].clone(),
+                depth,
+                size: totals[index],
+                is_dir,
+            });
+        }
+        entries
+    }
+
+    // Every directory and its total size, like `du`
+    pub fn du(&self) -> Vec<(FsPath, u64)> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.is_dir)
+            .map(|entry| (entry.path, entry.size))
+            .collect()
+    }
+
+    // Paths of every file or directory with the name, like `find -name`
+    pub fn find(&self, name: &str) -> Vec<FsPath> {
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.name == name)
+            .map(|entry| entry.path)
+            .collect()
+    }
+
+    // Draw the tree in the puzzle's format:
+    // - / (dir)
+    //   - a (dir)
+    //     - i (file, size=584)
+    pub fn tree(&self) -> String {
+        self.entries()
+            .iter()
+            .map(|entry| {
+                let indent = "  ".repeat(entry.depth);
+                if entry.is_dir {
+                    format!("{indent}- {} (dir)", entry.name)
+                } else {
+                    format!("{indent}- {} (file, size={})", entry.name, entry.size)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Converting between the tree and real directories
+impl FileSystem {
+    /*
+    Create the tree under `root`, which must be missing or empty. Files are
+    sized with set_len so they take no space where the file system supports
+    sparse files. Names that would leave the root, like "..", are refused.
+    */
+    pub fn materialise(&self, root: &Path) -> Result<()> {
+        if root.exists() && fs::read_dir(root)?.next().is_some() {
+            return Err(format!("{} is not empty.", root.display()).into());
+        }
+        let entries = self.entries();
+        if let Some(entry) = entries.iter().skip(1).find(|entry| {
+            matches!(entry.name.as_str(), "." | "..") || entry.name.contains(['/', '\\'])
+        }) {
+            return Err(format!("Can't create {}.", entry.path).into());
+        }
+        fs::create_dir_all(root)?;
+        for entry in entries.iter().skip(1) {
+            let path = entry
+                .path
+                .names()
+                .iter()
+                .fold(root.to_path_buf(), |path, name| path.join(name));
+            if entry.is_dir {
+                fs::create_dir(&path)?;
+            } else {
+                fs::File::create(&path)?.set_len(entry.size)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Read a real directory tree. Symbolic links and anything else that isn't
+    // a plain file or directory are left out, and names the log format can't
+    // hold are errors.
+    pub fn from_dir(root: &Path) -> Result<FileSystem> {
+        let mut tree = FileSystem::empty();
+        tree.read_dir(ROOT, root)?;
+        Ok(tree)
+    }
+
+    fn read_dir(&mut self, dir: usize, path: &Path) -> Result<()> {
+        let mut entries = fs::read_dir(path)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|name| format!("{name:?} is not UTF-8."))?;
+            if name.contains(char::is_whitespace) {
+                return Err(format!("'{name}' contains whitespace.").into());
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                let child = self.child(dir, &name, None)?;
+                self.read_dir(child, &entry.path())?;
+            } else if file_type.is_file() {
+                self.child(dir, &name, Some(entry.metadata()?.len()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // A terminal log that from_log turns back into this tree, listing each
+    // directory once then visiting its subdirectories in name order
+    pub fn to_log(&self) -> Vec<String> {
+        let mut lines = vec!["$ cd /".to_string()];
+        self.log_dir(ROOT, &mut lines);
+        lines
+    }
+
+    fn log_dir(&self, dir: usize, lines: &mut Vec<String>) {
+        let Node::Dir(children) = &self.nodes[dir] else {
+            return;
+        };
+        lines.push("$ ls".to_string());
+        for (name, child) in children {
+            lines.push(match self.nodes[*child] {
+                Node::Dir(_) => format!("dir {name}"),
+                Node::File(size) => format!("{size} {name}"),
+            });
+        }
+        for (name, child) in children {
+            if let Node::Dir(_) = self.nodes[*child] {
+                lines.push(format!("$ cd {name}"));
+                self.log_dir(*child, lines);
+                lines.push("$ cd ..".to_string());
+            }
+        }
+    }
+}
+
+// Replay the puzzle's log into a directory tree under `root`
+pub fn materialise_input(root: &Path) -> Result<()> {
+    FileSystem::from_log(common::get_input_lines("day7")?)?.materialise(root)
+}
+
+// Print a log describing a real directory tree
+pub fn print_log(root: &Path) -> Result<()> {
+    FileSystem::from_dir(root)?
+        .to_log()
+        .iter()
+        .for_each(|line| println!("{line}"));
+    Ok(())
+}
+
+// Directories to delete and what deleting them frees
+#[derive(Debug, PartialEq, Eq)]
+pub struct Plan {
+    pub paths: Vec<FsPath>,
+    pub freed: u64,
+    pub files: usize,
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "free {} bytes in {} files by deleting",
+            self.freed, self.files
+        )?;
+        if self.paths.is_empty() {
+            return write!(f, " nothing");
+        }
+        for path in &self.paths {
+            write!(f, " {path}")?;
+        }
+        Ok(())
+    }
+}
+
+// Per directory totals used for planning, indexed like the nodes
+struct DirStats {
+    sizes: Vec<u64>,
+    files: Vec<usize>,
+    children: Vec<Vec<usize>>,
+}
+
+// Choices of directories for the fewest_files search, keyed by how many
+// files they delete
+type Options = BTreeMap<u64, Vec<usize>>;
+
+impl FileSystem {
+    // Bytes that have to be deleted so `needed` bytes are free on a disk of
+    // `capacity` bytes
+    pub fn space_to_free(&self, capacity: u64, needed: u64) -> u64 {
+        (self.totals()[ROOT] + needed).saturating_sub(capacity)
+    }
+
+    fn dir_stats(&self) -> DirStats {
+        let mut files: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|node| usize::from(matches!(node, Node::File(_))))
+            .collect();
+        let mut children = vec![vec![]; self.nodes.len()];
+        for index in (1..self.nodes.len()).rev() {
+            files[self.parents[index]] += files[index];
+            if let Node::Dir(_) = self.nodes[index] {
+                children[self.parents[index]].push(index);
+            }
+        }
+        DirStats {
+            sizes: self.totals(),
+            files,
+            children,
+        }
+    }
+
+    fn plan(&self, mut dirs: Vec<usize>, stats: &DirStats) -> Plan {
+        dirs.sort_unstable();
+        let mut paths: Vec<FsPath> = dirs.iter().map(|dir| self.path(*dir)).collect();
+        paths.sort();
+        Plan {
+            paths,
+            freed: dirs.iter().map(|dir| stats.sizes[*dir]).sum(),
+            files: dirs.iter().map(|dir| stats.files[*dir]).sum(),
+        }
+    }
+
+    // The smallest single directory that frees enough space, as in part 2
+    pub fn smallest_directory(&self, capacity: u64, needed: u64) -> Option<Plan> {
+        let target = self.space_to_free(capacity, needed);
+        let stats = self.dir_stats();
+        (0..self.nodes.len())
+            .filter(|index| matches!(self.nodes[*index], Node::Dir(_)))
+            .filter(|dir| stats.sizes[*dir] >= target)
+            .min_by_key(|dir| stats.sizes[*dir])
+            .map(|dir| self.plan(vec![dir], &stats))
+    }
+
+    /*
+    The directories freeing the least space that is still enough, where no
+    chosen directory is inside another. A branch and bound knapsack over the
+    directories in tree order: each one is either deleted whole, skipping its
+    subdirectories, or kept while its subdirectories are considered. Branches
+    are cut once they can't reach the target or can't beat the best so far,
+    starting from the smallest single directory.
+    */
+    pub fn smallest_set(&self, capacity: u64, needed: u64) -> Option<Plan> {
+        let target = self.space_to_free(capacity, needed);
+        let stats = self.dir_stats();
+        let mut search = SetSearch {
+            target,
+            order: vec![],
+            ends: vec![],
+            reachable: vec![],
+            sizes: &stats.sizes,
+            chosen: vec![],
+            best: (u64::MAX, vec![]),
+        };
+        search.visit(ROOT, &stats);
+        search.reachable = vec![0; search.order.len() + 1];
+        for position in (0..search.order.len()).rev() {
+            search.reachable[position] =
+                stats.sizes[search.order[position]] + search.reachable[search.ends[position]];
+        }
+        if let Some(plan) = self.smallest_directory(capacity, needed) {
+            search.best = (
+                plan.freed,
+                plan.paths
+                    .iter()
+                    .flat_map(|path| self.lookup(path))
+                    .collect(),
+            );
+        }
+        search.branch(0, 0);
+        let (freed, dirs) = search.best;
+        (freed != u64::MAX).then(|| self.plan(dirs, &stats))
+    }
+
+    /*
+    The directories deleting the fewest files that still free enough space,
+    again with none inside another. Another knapsack over the tree, keyed by
+    the number of files deleted and keeping the most bytes freed for each.
+    There are only as many keys as files, so this one is always quick.
+    */
+    pub fn fewest_files(&self, capacity: u64, needed: u64) -> Option<Plan> {
+        let target = self.space_to_free(capacity, needed);
+        let stats = self.dir_stats();
+        let options = self.file_options(ROOT, &stats);
+        let (_, dirs) = options
+            .iter()
+            .find(|(_, dirs)| dirs.iter().map(|dir| stats.sizes[*dir]).sum::<u64>() >= target)?;
+        Some(self.plan(dirs.clone(), &stats))
+    }
+
+    fn file_options(&self, dir: usize, stats: &DirStats) -> Options {
+        let freed = |dirs: &[usize]| dirs.iter().map(|dir| stats.sizes[*dir]).sum::<u64>();
+        let keep_most =
+            |options: &mut Options, files: u64, dirs: Vec<usize>| match options.get(&files) {
+                Some(existing) if freed(existing) >= freed(&dirs) => {}
+                _ => {
+                    options.insert(files, dirs);
+                }
+            };
+        let mut options = Options::from([(0, vec![])]);
+        for child in &stats.children[dir] {
+            let child_options = self.file_options(*child, stats);
+            let mut combined = Options::new();
+            for (files, dirs) in &options {
+                for (child_files, child_dirs) in &child_options {
+                    let dirs = [dirs.as_slice(), child_dirs].concat();
+                    keep_most(&mut combined, files + child_files, dirs);
+                }
+            }
+            options = combined;
+        }
+        keep_most(&mut options, stats.files[dir] as u64, vec![dir]);
+        options
+    }
+}
+
+// State of the smallest_set search. Directories are numbered by their
+// position in tree order, and deleting the one at `position` skips ahead to
+// `ends[position]`, past its subdirectories.
+struct SetSearch<'a> {
+    target: u64,
+    order: Vec<usize>,
+    ends: Vec<usize>,
+    // The most that can be freed from each position on
+    reachable: Vec<u64>,
+    sizes: &'a [u64],
+    chosen: Vec<usize>,
+    best: (u64, Vec<usize>),
+}
+
+impl SetSearch<'_> {
+    fn visit(&mut self, dir: usize, stats: &DirStats) {
+        let position = self.order.len();
+        self.order.push(dir);
+        self.ends.push(0);
+        for child in &stats.children[dir] {
+            self.visit(*child, stats);
+        }
+        self.ends[position] = self.order.len();
+    }
+
+    fn branch(&mut self, position: usize, freed: u64) {
+        if freed >= self.target {
+            if freed < self.best.0 {
+                self.best = (freed, self.chosen.clone());
+            }
+            return;
+        }
+        if position == self.order.len()
+            || freed + self.reachable[position] < self.target
+            || self.best.0 == self.target
+        {
+            return;
+        }
+        let dir = self.order[position];
+        if freed + self.sizes[dir] < self.best.0 {
+            self.chosen.push(dir);
+            self.branch(self.ends[position], freed + self.sizes[dir]);
+            self.chosen.pop();
+        }
+        self.branch(position + 1, freed);
     }
 }
 
@@ -134,7 +711,346 @@ mod tests {
 
     #[test]
     fn test_parse_file() -> Result<()> {
-        assert_eq!(parse_line("123 f".to_string()), Some(Log::File(123)));
+        assert_eq!(
+            parse_line("123 f".to_string()),
+            Some(Log::File(123, "f".to_string()))
+        );
+        Ok(())
+    }
+
+    const SAMPLE: &str = "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+    fn sample() -> Result<FileSystem> {
+        FileSystem::from_log(SAMPLE.lines().map(String::from))
+    }
+
+    fn du_strings(fs: &FileSystem) -> Vec<(String, u64)> {
+        fs.du()
+            .into_iter()
+            .map(|(path, size)| (path.to_string(), size))
+            .collect()
+    }
+
+    #[test]
+    fn test_file_system_queries() -> Result<()> {
+        let fs = sample()?;
+        assert_eq!(
+            du_strings(&fs),
+            [
+                ("/".to_string(), 48381165),
+                ("/a".to_string(), 94853),
+                ("/a/e".to_string(), 584),
+                ("/d".to_string(), 24933642)
+            ]
+        );
+        assert_eq!(fs.size(&"/a/e/i".parse()?), Some(584));
+        assert_eq!(fs.size(&"/d/".parse()?), Some(24933642));
+        assert_eq!(fs.size(&"/a/i".parse()?), None);
+        assert_eq!(fs.find("d.log"), ["/d/d.log".parse()?]);
+        assert_eq!(fs.find("e")[0].to_string(), "/a/e");
+        let tree = fs.tree();
+        assert_eq!(
+            tree.lines().take(5).collect::<Vec<_>>(),
+            [
+                "- / (dir)",
+                "  - a (dir)",
+                "    - e (dir)",
+                "      - i (file, size=584)",
+                "    - f (file, size=29116)"
+            ]
+        );
+        assert_eq!(tree.lines().count(), 14);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_system_log_quirks() -> Result<()> {
+        // Jumping back to the root, listing twice and entering a directory
+        // that was never listed
+        let log = "$ cd /
+$ ls
+100 a
+$ cd x
+$ cd y
+$ ls
+20 b
+$ cd /
+$ ls
+100 a
+dir x
+$ cd x
+$ cd y
+$ ls
+20 b
+3 c
+$ cd ..
+$ cd ..
+$ cd ..";
+        let fs = FileSystem::from_log(log.lines().map(String::from))?;
+        assert_eq!(
+            du_strings(&fs),
+            [
+                ("/".to_string(), 123),
+                ("/x".to_string(), 23),
+                ("/x/y".to_string(), 23)
+            ]
+        );
+
+        // The repeated listings count once, unlike in get_sizes
+        assert_eq!(part1(log.lines().map(String::from))?, 123 + 23 + 23);
+        assert_eq!(
+            get_sizes(log.lines().map(String::from)).get(&FsPath::root()),
+            Some(&(2 * 100 + 20 + 23))
+        );
+
+        let conflict = "$ cd /\n$ ls\n100 a\n$ cd a";
+        let error = FileSystem::from_log(conflict.lines().map(String::from)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 4: 'a' is both a file and a directory."
+        );
+        assert!(FileSystem::from_log(["$ rm -rf /".to_string()].into_iter()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_system_matches_get_sizes() -> Result<()> {
+        let fs = FileSystem::from_log(common::get_input_lines("day7")?)?;
+        let sizes = get_sizes(common::get_input_lines("day7")?);
+        assert_eq!(fs.du().len(), sizes.len());
+        assert_eq!(
+            fs.size(&FsPath::root()),
+            sizes.get(&FsPath::root()).copied()
+        );
+        for (path, size) in fs.du() {
+            assert_eq!(sizes.get(&path), Some(&size));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fs_path() -> Result<()> {
+        let path: FsPath = "/a/bc/".parse()?;
+        assert_eq!(path.names(), ["a", "bc"]);
+        assert_eq!(path.to_string(), "/a/bc");
+        assert_eq!(FsPath::root().to_string(), "/");
+        assert_eq!(FsPath::root().join("a").join("bc"), path);
+        assert_eq!(path.parent(), Some("/a".parse()?));
+        assert_eq!(FsPath::root().parent(), None);
+        let ancestors: Vec<String> = path.ancestors().map(|p| p.to_string()).collect();
+        assert_eq!(ancestors, ["/a/bc", "/a", "/"]);
+        assert!("a/bc".parse::<FsPath>().is_err());
+        Ok(())
+    }
+
+    // Joining names without a separator made /a/bc and /ab/c the same key,
+    // and /a/b the same as the file-less /ab
+    #[test]
+    fn test_colliding_paths_stay_apart() -> Result<()> {
+        let log = "$ cd /
+$ ls
+dir a
+dir ab
+$ cd a
+$ ls
+dir bc
+dir b
+$ cd bc
+$ ls
+1 x
+$ cd ..
+$ cd b
+$ ls
+10 x
+$ cd /
+$ cd ab
+$ ls
+dir c
+$ cd c
+$ ls
+100 y";
+        let sizes = get_sizes(log.lines().map(String::from));
+        let size = |path: &str| -> Result<Option<u64>> { Ok(sizes.get(&path.parse()?).copied()) };
+        assert_eq!(size("/a/bc")?, Some(1));
+        assert_eq!(size("/ab/c")?, Some(100));
+        assert_eq!(size("/a/b")?, Some(10));
+        assert_eq!(size("/ab")?, Some(100));
+        assert_eq!(size("/a")?, Some(11));
+        assert_eq!(size("/")?, Some(111));
+        assert_eq!(sizes.len(), 6);
+
+        let fs = FileSystem::from_log(log.lines().map(String::from))?;
+        assert_eq!(
+            du_strings(&fs),
+            [
+                ("/".to_string(), 111),
+                ("/a".to_string(), 11),
+                ("/a/b".to_string(), 10),
+                ("/a/bc".to_string(), 1),
+                ("/ab".to_string(), 100),
+                ("/ab/c".to_string(), 100)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cleanup_plans() -> Result<()> {
+        let log = "$ cd /
+$ ls
+5 r
+dir x
+dir y
+dir z
+$ cd x
+$ ls
+20 a
+20 b
+20 c
+$ cd ..
+$ cd y
+$ ls
+10 a
+10 b
+10 c
+10 d
+10 e
+$ cd ..
+$ cd z
+$ ls
+5 f
+dir w
+$ cd w
+$ ls
+40 g";
+        let fs = FileSystem::from_log(log.lines().map(String::from))?;
+        assert_eq!(fs.space_to_free(160, 95), 95);
+        let plan = fs.smallest_directory(160, 95).ok_or("No plan.")?;
+        assert_eq!(plan.to_string(), "free 160 bytes in 11 files by deleting /");
+        let plan = fs.smallest_set(160, 95).ok_or("No plan.")?;
+        assert_eq!(
+            plan.to_string(),
+            "free 95 bytes in 7 files by deleting /y /z"
+        );
+        let plan = fs.fewest_files(160, 95).ok_or("No plan.")?;
+        assert_eq!(
+            plan.to_string(),
+            "free 100 bytes in 4 files by deleting /x /z/w"
+        );
+
+        let plan = fs.smallest_set(1000, 95).ok_or("No plan.")?;
+        assert_eq!(
+            plan.to_string(),
+            "free 0 bytes in 0 files by deleting nothing"
+        );
+        assert_eq!(fs.fewest_files(100, 1000), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cleanup_plans_on_input() -> Result<()> {
+        let fs = FileSystem::from_log(common::get_input_lines("day7")?)?;
+        let target = fs.space_to_free(DISK_CAPACITY, SPACE_NEEDED);
+        let single = fs
+            .smallest_directory(DISK_CAPACITY, SPACE_NEEDED)
+            .ok_or("No plan.")?;
+        assert_eq!(single.freed, 9608311);
+        assert_eq!(part2(common::get_input_lines("day7")?)?, Some(9608311));
+        let corrupt = ["$ cd /", "$ ls", "lots a"].map(String::from);
+        assert_eq!(
+            part2(corrupt.into_iter()).unwrap_err().to_string(),
+            "Line 3: can't parse 'lots a'."
+        );
+        assert_eq!(part2(["$ cd /".to_string()].into_iter())?, Some(0));
+        let set = fs
+            .smallest_set(DISK_CAPACITY, SPACE_NEEDED)
+            .ok_or("No plan.")?;
+        assert!(target <= set.freed && set.freed <= single.freed);
+        let fewest = fs
+            .fewest_files(DISK_CAPACITY, SPACE_NEEDED)
+            .ok_or("No plan.")?;
+        assert!(target <= fewest.freed && fewest.files <= single.files);
+        // No chosen directory is inside another
+        for plan in [set, fewest] {
+            for path in &plan.paths {
+                let nested = plan
+                    .paths
+                    .iter()
+                    .filter(|other| other.ancestors().any(|a| &a == path));
+                assert_eq!(nested.count(), 1);
+            }
+        }
+        Ok(())
+    }
+
+    // A fresh directory for a test, removed first in case an earlier run
+    // left it behind
+    fn scratch_dir(name: &str) -> Result<std::path::PathBuf> {
+        let dir = std::env::temp_dir().join(format!("aoc_2022_day7_{name}_{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_materialise_round_trip() -> Result<()> {
+        let dir = scratch_dir("round_trip")?;
+        let tree = sample()?;
+        tree.materialise(&dir)?;
+        assert_eq!(fs::metadata(dir.join("d").join("d.log"))?.len(), 8033020);
+        assert!(tree.materialise(&dir).is_err());
+
+        let read = FileSystem::from_dir(&dir)?;
+        fs::remove_dir_all(&dir)?;
+        assert_eq!(read.tree(), tree.tree());
+        let replayed = FileSystem::from_log(read.to_log().into_iter())?;
+        assert_eq!(replayed.tree(), tree.tree());
+        assert_eq!(
+            read.to_log()[..4],
+            ["$ cd /", "$ ls", "dir a", "14848514 b.txt"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_log_matches_input() -> Result<()> {
+        let tree = FileSystem::from_log(common::get_input_lines("day7")?)?;
+        let replayed = FileSystem::from_log(tree.to_log().into_iter())?;
+        assert_eq!(replayed.du(), tree.du());
+        assert_eq!(
+            part1(tree.to_log().into_iter())?,
+            part1(common::get_input_lines("day7")?)?
+        );
+        assert_eq!(part1(common::get_input_lines("day7")?)?, 1206825);
+
+        let escaping =
+            FileSystem::from_log(["$ cd /", "$ ls", "dir .."].map(String::from).into_iter())?;
+        let dir = scratch_dir("escaping")?;
+        assert!(escaping.materialise(&dir).is_err());
+        assert!(!dir.exists());
         Ok(())
     }
 }
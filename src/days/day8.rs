@@ -1,73 +1,390 @@
+use crate::days::common::visualize;
 use crate::days::common::Result;
-use std::{collections::HashSet, fs};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
 
 use super::common;
 // extern crate test;
 
-pub fn solution<'a>() -> Result<()> {
+pub fn solution() -> Result<()> {
     println!("~~~~~~~~~~~~~ Day 8 ~~~~~~~~~~~~~");
     let input = fs::read_to_string("./input/day8.txt")?;
-    println!("Part 1: {}", part1(&input).ok_or("Empty input.")?);
-    println!("Part 2: {}", part2(&input).ok_or("Empty input.")?);
+    println!("Part 1: {}", part1(&input)?);
+    println!("Part 2: {}", part2(&input)?.ok_or("Empty input.")?);
     Ok(())
 }
 
-fn part1(input: &str) -> Option<usize> {
-    let rows = build_grid1(input);
-    let cols = common::transpose(&rows);
-    let num_edge_trees = 2 * (rows.len() + cols.len()) - 4;
-    let lr_set = visible(&rows);
-    let tb_set = visible(&cols);
-    Some(num_edge_trees + (&lr_set | &tb_set).len())
+// Trees that can be seen from outside the forest
+fn part1(input: &str) -> Result<usize> {
+    let rows = parse_heights(input)?;
+    Ok(visible_trees(&rows)
+        .iter()
+        .flatten()
+        .filter(|tree| **tree)
+        .count())
+}
+
+fn part2(input: &str) -> Result<Option<usize>> {
+    let rows = parse_heights(input)?;
+    Ok(scenic_scores(&rows).into_iter().flatten().max())
+}
+
+// Rows of tree heights 0 to 9, all the same length. No lines at all is an
+// empty forest.
+pub fn parse_heights(input: &str) -> Result<Vec<Vec<u8>>> {
+    let mut rows: Vec<Vec<u8>> = vec![];
+    for (line_number, line) in (1..).zip(input.lines()) {
+        let row = (1..)
+            .zip(line.chars())
+            .map(|(column, tree)| match tree.to_digit(10) {
+                Some(height) => Ok(height as u8),
+                None => Err(format!(
+                    "Line {line_number}, column {column}: '{tree}' is not a height."
+                )),
+            })
+            .collect::<std::result::Result<Vec<u8>, String>>()?;
+        if let Some(first) = rows.first() {
+            if row.len() != first.len() {
+                return Err(format!(
+                    "Line {line_number}: {} trees but line 1 has {}.",
+                    row.len(),
+                    first.len()
+                )
+                .into());
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// How far a tree can see in each direction before a tree at least as tall
+// blocks the view, or the edge of the forest is reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Distances {
+    pub up: usize,
+    pub down: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+// What a tree sees looking one way: how far, and whether all the way out of
+// the forest without any tree at least as tall in between.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct View {
+    pub distance: usize,
+    pub clear: bool,
+}
+
+impl Distances {
+    pub fn scenic_score(&self) -> usize {
+        self.up * self.down * self.left * self.right
+    }
+}
+
+pub fn viewing_distances<T: Ord>(rows: &[Vec<T>]) -> Vec<Vec<Distances>> {
+    let mut distances: Vec<Vec<Distances>> = rows
+        .iter()
+        .map(|row| vec![Distances::default(); row.len()])
+        .collect();
+    sweep_views(rows, |direction, i, j, View { distance, .. }| {
+        let tree = &mut distances[i][j];
+        match direction {
+            Direction::Up => tree.up = distance,
+            Direction::Down => tree.down = distance,
+            Direction::Left => tree.left = distance,
+            Direction::Right => tree.right = distance,
+        }
+    });
+    distances
+}
+
+// Multiplies the distances straight into the scores rather than keeping all
+// four per tree, which matters for very large forests.
+pub fn scenic_scores<T: Ord>(rows: &[Vec<T>]) -> Vec<Vec<usize>> {
+    let mut scores: Vec<Vec<usize>> = rows.iter().map(|row| vec![1; row.len()]).collect();
+    sweep_views(rows, |_, i, j, view| scores[i][j] *= view.distance);
+    scores
+}
+
+// Everything seen from one tree, with its position counted from 0 at the top
+// left of the forest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tree {
+    pub row: usize,
+    pub col: usize,
+    pub height: u8,
+    pub up: View,
+    pub down: View,
+    pub left: View,
+    pub right: View,
+}
+
+impl Tree {
+    pub fn visible(&self) -> bool {
+        self.up.clear || self.down.clear || self.left.clear || self.right.clear
+    }
+
+    pub fn distances(&self) -> Distances {
+        Distances {
+            up: self.up.distance,
+            down: self.down.distance,
+            left: self.left.distance,
+            right: self.right.distance,
+        }
+    }
+
+    pub fn scenic_score(&self) -> usize {
+        self.distances().scenic_score()
+    }
 }
 
-fn part2(input: &str) -> Option<usize> {
-    let rows = build_grid2(input);
-    let cols = common::transpose(&rows);
-    let row_score_vecs = compute_row_scores(rows);
+// Every tree in the forest, a row at a time
+pub fn survey(rows: &[Vec<u8>]) -> Vec<Tree> {
+    let mut trees: Vec<Vec<Tree>> = (0..)
+        .zip(rows)
+        .map(|(row, heights)| {
+            (0..)
+                .zip(heights)
+                .map(|(col, height)| Tree {
+                    row,
+                    col,
+                    height: *height,
+                    up: View::default(),
+                    down: View::default(),
+                    left: View::default(),
+                    right: View::default(),
+                })
+                .collect()
+        })
+        .collect();
+    sweep_views(rows, |direction, i, j, view| {
+        let tree = &mut trees[i][j];
+        match direction {
+            Direction::Up => tree.up = view,
+            Direction::Down => tree.down = view,
+            Direction::Left => tree.left = view,
+            Direction::Right => tree.right = view,
+        }
+    });
+    trees.into_iter().flatten().collect()
+}
+
+// The n trees with the best scenic scores, best first. Keeps a min heap of at
+// most n like `day1::top_k`, with ties going to the tree nearer the top left.
+pub fn top_trees(trees: &[Tree], n: usize) -> Vec<&Tree> {
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+    for (index, tree) in trees.iter().enumerate() {
+        heap.push(Reverse((tree.scenic_score(), Reverse(index))));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((_, Reverse(index)))| &trees[index])
+        .collect()
+}
+
+pub fn write_csv(out: &mut impl Write, trees: &[Tree]) -> Result<()> {
+    writeln!(
+        out,
+        "row,col,height,\
+         visible_up,visible_down,visible_left,visible_right,\
+         distance_up,distance_down,distance_left,distance_right,scenic_score"
+    )?;
+    for tree in trees {
+        let views = [tree.up, tree.down, tree.left, tree.right];
+        let visible = views.map(|view| view.clear.to_string()).join(",");
+        let distances = views.map(|view| view.distance.to_string()).join(",");
+        writeln!(
+            out,
+            "{},{},{},{visible},{distances},{}",
+            tree.row,
+            tree.col,
+            tree.height,
+            tree.scenic_score()
+        )?;
+    }
+    Ok(())
+}
+
+// The scenic scores as a PPM image with `scale` pixels to a side for each tree
+pub fn write_heatmap(out: &mut impl Write, scores: &[Vec<usize>], scale: usize) -> Result<()> {
+    let best = scores.iter().flatten().max().copied().unwrap_or(0);
+    let width = scores.first().map_or(0, Vec::len) * scale;
+    let mut pixels = Vec::with_capacity(width * scores.len() * scale * 3);
+    for row in scores {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|score| std::iter::repeat_n(heat(*score, best), scale))
+            .flatten()
+            .collect();
+        for _ in 0..scale {
+            pixels.extend(&line);
+        }
+    }
+    visualize::write_ppm(out, width, scores.len() * scale, &pixels)
+}
+
+/*
+False colour for a score, going from black through purple and orange to pale
+yellow for the best one. A few trees score far higher than the rest, so the
+scale is logarithmic to keep the others from all coming out black.
+*/
+fn heat(score: usize, best: usize) -> [u8; 3] {
+    const STOPS: [[f64; 3]; 4] = [
+        [0.0, 0.0, 4.0],
+        [120.0, 28.0, 109.0],
+        [237.0, 105.0, 37.0],
+        [252.0, 255.0, 164.0],
+    ];
+    let level = match best {
+        0 => 0.0,
+        _ => (score as f64).ln_1p() / (best as f64).ln_1p() * 3.0,
+    };
+    let stop = (level as usize).min(2);
+    let fraction = level - stop as f64;
+    [0, 1, 2].map(|channel| {
+        let (from, to) = (STOPS[stop][channel], STOPS[stop + 1][channel]);
+        (from + (to - from) * fraction).round() as u8
+    })
+}
+
+// Write trees.csv and scenic.ppm for the input into `dir` and list the best
+// trees.
+pub fn export(dir: &Path, top: usize) -> Result<()> {
+    let rows = parse_heights(&fs::read_to_string("./input/day8.txt")?)?;
+    let trees = survey(&rows);
+    fs::create_dir_all(dir)?;
+    let mut csv = BufWriter::new(File::create(dir.join("trees.csv"))?);
+    write_csv(&mut csv, &trees)?;
+    csv.flush()?;
+    let mut ppm = BufWriter::new(File::create(dir.join("scenic.ppm"))?);
+    write_heatmap(&mut ppm, &scenic_scores(&rows), 4)?;
+    ppm.flush()?;
+    for tree in top_trees(&trees, top) {
+        println!(
+            "row {} col {} height {}: score {}",
+            tree.row,
+            tree.col,
+            tree.height,
+            tree.scenic_score()
+        );
+    }
+    Ok(())
+}
+
+// Trees with a clear view out of the forest in at least one direction. Every
+// tree on the edge has one, whatever the shape of the forest.
+pub fn visible_trees<T: Ord>(rows: &[Vec<T>]) -> Vec<Vec<bool>> {
+    let mut visible: Vec<Vec<bool>> = rows.iter().map(|row| vec![false; row.len()]).collect();
+    sweep_views(rows, |_, i, j, view| visible[i][j] |= view.clear);
+    visible
+}
+
+// Calls `record(direction, i, j, view)` for every tree looking in each
+// direction. The columns are swept a row at a time with a stack for each, so
+// everything is visited in the order it is laid out in memory.
+fn sweep_views<T: Ord>(rows: &[Vec<T>], mut record: impl FnMut(Direction, usize, usize, View)) {
+    let mut stack = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        stack.clear();
+        for (j, height) in row.iter().enumerate() {
+            record(Direction::Left, i, j, look_back(&mut stack, j, height));
+        }
+        stack.clear();
+        for (step, (j, height)) in row.iter().enumerate().rev().enumerate() {
+            record(Direction::Right, i, j, look_back(&mut stack, step, height));
+        }
+    }
+    let width = rows.first().map_or(0, Vec::len);
+    let mut stacks = vec![vec![]; width];
+    for (i, row) in rows.iter().enumerate() {
+        for (j, height) in row.iter().enumerate() {
+            record(Direction::Up, i, j, look_back(&mut stacks[j], i, height));
+        }
+    }
+    stacks.iter_mut().for_each(Vec::clear);
+    for (step, (i, row)) in rows.iter().enumerate().rev().enumerate() {
+        for (j, height) in row.iter().enumerate() {
+            record(
+                Direction::Down,
+                i,
+                j,
+                look_back(&mut stacks[j], step, height),
+            );
+        }
+    }
+}
+
+/*
+How many trees the tree `step` trees along a line sees looking back towards
+the start of it. The stack holds the trees that could still block a later
+view, never getting taller from bottom to top: a tree shorter than the
+current one can't block anything further along, since the current tree is
+both closer and taller. Whatever is left on the stack blocks the view, so
+an empty one means it is clear. Every tree is pushed and popped at most once,
+so a whole line takes O(n).
+*/
+fn look_back<'a, T: Ord>(stack: &mut Vec<(usize, &'a T)>, step: usize, height: &'a T) -> View {
+    while stack.last().is_some_and(|(_, top)| *top < height) {
+        stack.pop();
+    }
+    let view = match stack.last() {
+        Some((blocker, _)) => View {
+            distance: step - blocker,
+            clear: false,
+        },
+        None => View {
+            distance: step,
+            clear: true,
+        },
+    };
+    stack.push((step, height));
+    view
+}
+
+// Walks outward from every tree in both directions along the rows and then
+// the columns. The original version, kept as a reference for the stack one.
+#[allow(dead_code)]
+fn part2_reference(input: &str) -> Result<Option<usize>> {
+    let rows = parse_heights(input)?;
+    Ok(scenic_scores_reference(&rows).into_iter().flatten().max())
+}
+
+pub fn scenic_scores_reference<T: Ord + Copy>(rows: &[Vec<T>]) -> Vec<Vec<usize>> {
+    let cols = common::transpose(rows);
+    let row_score_vecs = compute_row_scores(rows.to_vec());
     let col_score_vecs: Vec<Vec<usize>> = common::transpose(&compute_row_scores(cols));
 
     row_score_vecs
         .iter()
         .zip(col_score_vecs.iter())
-        .flat_map(|(r_scores, c_scores)| {
+        .map(|(r_scores, c_scores)| {
             r_scores
                 .iter()
                 .zip(c_scores.iter())
                 .map(|(r_score, c_score)| r_score * c_score)
-                .max()
+                .collect()
         })
-        .max()
-}
-
-fn visible<T: Ord>(rows: &Vec<Vec<(usize, usize, T)>>) -> HashSet<(&usize, &usize)> {
-    let mut visible_set = HashSet::<(&usize, &usize)>::new();
-    for row in &rows[1..rows.len() - 1] {
-        // Left pass
-        let mut tallest = &row[0].2;
-        for (i, j, tree) in &row[1..row.len() - 1] {
-            if tree > tallest {
-                visible_set.insert((i, j));
-                tallest = tree;
-            }
-        }
-
-        // Right pass
-        let mut tallest = &row[row.len() - 1].2;
-        for (i, j, tree) in row[1..row.len() - 1].iter().rev() {
-            if visible_set.contains(&(i, j)) {
-                break;
-            }
-            if tree > tallest {
-                visible_set.insert((i, j));
-                tallest = tree
-            }
-        }
-    }
-    visible_set
+        .collect()
 }
 
-fn compute_row_scores(rows: Vec<Vec<char>>) -> Vec<Vec<usize>> {
+fn compute_row_scores<T: Ord>(rows: Vec<Vec<T>>) -> Vec<Vec<usize>> {
     rows.iter()
         .map(|row| {
             row.iter()
@@ -103,26 +420,10 @@ fn view_score<T: Ord>(index: usize, row: &Vec<T>) -> usize {
     count_left * count_right
 }
 
-fn build_grid1(input: &str) -> Vec<Vec<(usize, usize, char)>> {
-    input
-        .lines()
-        .enumerate()
-        .map(|(i, line)| {
-            line.chars()
-                .enumerate()
-                .map(|(j, byte)| (i, j, byte))
-                .collect()
-        })
-        .collect()
-}
-
-fn build_grid2(input: &str) -> Vec<Vec<char>> {
-    input.lines().map(|line| line.chars().collect()).collect()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::days::common::random::Lcg;
     // use test::Bencher;
 
     #[test]
@@ -135,14 +436,165 @@ mod tests {
     #[test]
     fn test_part_one() -> Result<()> {
         let input = fs::read_to_string(format!("./input/day8.txt"))?;
-        assert_eq!(part1(&input), Some(1533));
+        assert_eq!(part1(&input)?, 1533);
         Ok(())
     }
 
     #[test]
     fn test_part_two() -> Result<()> {
         let input = fs::read_to_string("./input/day8.txt")?;
-        assert_eq!(part2(&input), Some(345744));
+        assert_eq!(part2(&input)?, Some(345744));
+        Ok(())
+    }
+
+    // A forest of random heights from a fixed seed
+    fn generate_forest(rows: usize, cols: usize, seed: u64) -> String {
+        let mut lcg = Lcg::new(seed);
+        let mut forest = String::with_capacity(rows * (cols + 1));
+        for _ in 0..rows {
+            for _ in 0..cols {
+                forest.push(char::from(b'0' + lcg.below(10) as u8));
+            }
+            forest.push('\n');
+        }
+        forest
+    }
+
+    #[test]
+    fn test_viewing_distances() -> Result<()> {
+        let rows = parse_heights("30373\n25512\n65332\n33549\n35390")?;
+        let distances = viewing_distances(&rows);
+        assert_eq!(
+            distances[3][2],
+            Distances {
+                up: 2,
+                down: 1,
+                left: 2,
+                right: 2
+            }
+        );
+        assert_eq!(distances[3][2].scenic_score(), 8);
+        assert_eq!(
+            distances[0][0],
+            Distances {
+                up: 0,
+                down: 2,
+                left: 0,
+                right: 2
+            }
+        );
+        let scores = scenic_scores(&rows);
+        for (row, distances) in scores.iter().zip(&distances) {
+            let expected: Vec<usize> = distances.iter().map(Distances::scenic_score).collect();
+            assert_eq!(*row, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_degenerate_forests() -> Result<()> {
+        assert_eq!(parse_heights("")?, Vec::<Vec<u8>>::new());
+        assert_eq!(part1("")?, 0);
+        assert_eq!(part2("")?, None);
+
+        for forest in ["5", "30373", "3\n0\n3\n7\n3"] {
+            let trees = forest.chars().filter(char::is_ascii_digit).count();
+            assert_eq!(part1(forest)?, trees);
+            assert_eq!(part2(forest)?, Some(0));
+        }
+        let column = parse_heights("3\n0\n3\n7\n3")?;
+        assert_eq!(viewing_distances(&column)[2][0].up, 2);
+        assert_eq!(viewing_distances(&column)[2][0].down, 1);
+
+        // Only the middle tree is hidden, and the tall one in it sees the edges
+        assert_eq!(part1("999\n989\n999")?, 8);
+        assert_eq!(part1("111\n151\n111")?, 9);
+        assert_eq!(part2("111\n151\n111")?, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_heights() -> Result<()> {
+        let error = |input| parse_heights(input).unwrap_err().to_string();
+        assert_eq!(error("303\n2x5"), "Line 2, column 2: 'x' is not a height.");
+        assert_eq!(error("30373\n2551"), "Line 2: 4 trees but line 1 has 5.");
+        assert_eq!(error("30 3"), "Line 1, column 3: ' ' is not a height.");
+        assert!(part1("30373\n\n30373").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_survey() -> Result<()> {
+        let rows = parse_heights("30373\n25512\n65332\n33549\n35390")?;
+        let trees = survey(&rows);
+        assert_eq!(trees.len(), 25);
+        let tree = trees[3 * 5 + 2];
+        assert_eq!((tree.row, tree.col, tree.height), (3, 2, 5));
+        assert_eq!(tree.distances(), viewing_distances(&rows)[3][2]);
+        assert!(tree.visible() && !tree.up.clear && tree.down.clear && tree.left.clear);
+        let visible = visible_trees(&rows);
+        assert!(trees
+            .iter()
+            .all(|tree| tree.visible() == visible[tree.row][tree.col]));
+
+        let best: Vec<(usize, usize, usize)> = top_trees(&trees, 3)
+            .iter()
+            .map(|tree| (tree.row, tree.col, tree.scenic_score()))
+            .collect();
+        assert_eq!(best, [(3, 2, 8), (2, 1, 6), (1, 2, 4)]);
+        assert_eq!(top_trees(&trees, 30).len(), 25);
+        let flat = survey(&parse_heights("111\n111\n111")?);
+        let best: Vec<(usize, usize)> = top_trees(&flat, 3)
+            .iter()
+            .map(|tree| (tree.row, tree.col))
+            .collect();
+        assert_eq!(best, [(1, 1), (0, 0), (0, 1)]);
+
+        let mut csv = vec![];
+        write_csv(&mut csv, &trees)?;
+        let csv = String::from_utf8(csv)?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 26);
+        assert!(lines[0].starts_with("row,col,height,visible_up,"));
+        assert_eq!(lines[18], "3,2,5,false,true,true,false,2,1,2,2,8");
+
+        let mut ppm = vec![];
+        write_heatmap(&mut ppm, &scenic_scores(&rows), 2)?;
+        let header = b"P6\n10 10\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 10 * 10 * 3);
+        // The best tree gets the top colour and any scoring 0 the bottom one
+        let pixel = |x: usize, y: usize| &ppm[header.len() + (y * 10 + x) * 3..][..3];
+        assert_eq!(pixel(4, 6), [252, 255, 164]);
+        assert_eq!(pixel(0, 0), [0, 0, 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_reference() -> Result<()> {
+        let input = fs::read_to_string("./input/day8.txt")?;
+        assert_eq!(part2(&input)?, part2_reference(&input)?);
+        for seed in 0..20 {
+            let forest = generate_forest(30, 40, seed);
+            assert_eq!(part2(&forest)?, part2_reference(&forest)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_reference_on_slopes_and_degenerate_forests() -> Result<()> {
+        // Every tree is one taller than those above and to the left of it, so
+        // it sees right back to the top and left edges but only one tree down
+        // and right
+        let slope: Vec<Vec<u16>> = (0..40).map(|i| (0..60).map(|j| i + j).collect()).collect();
+        let scores = scenic_scores(&slope);
+        assert_eq!(scores, scenic_scores_reference(&slope));
+        assert_eq!(scores.into_iter().flatten().max(), Some(38 * 58));
+
+        for forest in ["", "5", "30373", "3\n0\n3\n7\n3", "99\n99", "111\n151\n111"] {
+            let rows = parse_heights(forest)?;
+            assert_eq!(scenic_scores(&rows), scenic_scores_reference(&rows));
+        }
         Ok(())
     }
 
@@ -1,12 +1,10 @@
+use crate::days::common::trace::{Event, NoTrace, Trace, Tracer};
+use crate::days::common::visualize::{Frame, Visualize};
 use crate::days::common::Result;
-use std::{
-    collections::{HashSet, VecDeque},
-    fs,
-    iter::repeat,
-};
+use std::{collections::HashSet, fs, iter::repeat};
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
-struct Point(i16, i16);
+pub struct Point(pub i16, pub i16);
 
 impl Point {
     fn difference(&self, other: &Point) -> (i16, i16) {
@@ -14,63 +12,315 @@ impl Point {
     }
 }
 
-#[derive(Clone)]
-enum Direction {
+// Ways the head can move in one step, up being towards positive y
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
     Up,
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    fn offset(&self) -> (i16, i16) {
+        match self {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+            Direction::UpLeft => (-1, 1),
+            Direction::UpRight => (1, 1),
+            Direction::DownLeft => (-1, -1),
+            Direction::DownRight => (1, -1),
+        }
+    }
+}
+
+/*
+A rope of knots starting on top of each other at the origin, the head first.
+The knots are allocated once when the rope is made and moved in place, and
+every knot keeps the set of positions it has been in.
+*/
+#[derive(Clone, Debug)]
+pub struct Rope {
+    knots: Vec<Point>,
+    visited: Vec<HashSet<Point>>,
+}
+
+impl Rope {
+    pub fn new(length: usize) -> Result<Self> {
+        if length == 0 {
+            return Err("A rope needs at least one knot.".into());
+        }
+        Ok(Rope {
+            knots: vec![Point(0, 0); length],
+            visited: vec![HashSet::from([Point(0, 0)]); length],
+        })
+    }
+
+    pub fn knots(&self) -> &[Point] {
+        &self.knots
+    }
+
+    pub fn head(&self) -> Point {
+        self.knots[0]
+    }
+
+    pub fn tail(&self) -> Point {
+        self.knots[self.knots.len() - 1]
+    }
+
+    // Positions each knot has been in, in the same order as the knots
+    pub fn visited(&self) -> &[HashSet<Point>] {
+        &self.visited
+    }
+
+    // Move the head one step, dragging the rest of the rope along. Once a knot
+    // stays put so does everything behind it, so this reports how many knots
+    // moved counting from the head.
+    pub fn step(&mut self, direction: Direction) -> usize {
+        let (x, y) = direction.offset();
+        self.knots[0] = Point(self.knots[0].0 + x, self.knots[0].1 + y);
+        let mut moved = 1;
+        while moved < self.knots.len() {
+            let (x_diff, y_diff) = self.knots[moved - 1].difference(&self.knots[moved]);
+            if x_diff.abs() < 2 && y_diff.abs() < 2 {
+                break;
+            }
+            let knot = &mut self.knots[moved];
+            *knot = Point(knot.0 + x_diff.signum(), knot.1 + y_diff.signum());
+            moved += 1;
+        }
+        for (knot, visited) in self.knots.iter().zip(&mut self.visited).take(moved) {
+            visited.insert(*knot);
+        }
+        moved
+    }
+
+    // Take each step in turn, handing the knots after it to `visit` along with
+    // the step number. They are lent straight from the rope, so nothing is
+    // copied or allocated per step.
+    pub fn steps(
+        &mut self,
+        directions: impl IntoIterator<Item = Direction>,
+        mut visit: impl FnMut(usize, &[Point]),
+    ) {
+        for (step, direction) in directions.into_iter().enumerate() {
+            self.step(direction);
+            visit(step, &self.knots);
+        }
+    }
+
+    /*
+    The same steps as an iterator, so they can be zipped, cut short or
+    collected. An iterator can't lend out the rope's own knots, so each step
+    copies them into an array the length of the rope instead, which still
+    stays off the heap. N has to match the number of knots.
+    */
+    pub fn positions<'a, const N: usize>(
+        &'a mut self,
+        directions: impl IntoIterator<Item = Direction> + 'a,
+    ) -> Result<impl Iterator<Item = [Point; N]> + 'a> {
+        if N != self.knots.len() {
+            return Err(format!("The rope has {} knots, not {N}.", self.knots.len()).into());
+        }
+        Ok(directions.into_iter().map(move |direction| {
+            self.step(direction);
+            std::array::from_fn(|knot| self.knots[knot])
+        }))
+    }
 }
 
 const INPUT: &str = "./input/day9.txt";
-pub fn solution<'a>() -> Result<()> {
+pub fn solution() -> Result<()> {
     println!("~~~~~~~~~~~~~ Day 9 ~~~~~~~~~~~~~");
     let input = fs::read_to_string(INPUT)?;
-    println!("Part 1: {}", count_unique_tail_positions(&input, 2));
-    println!("Part 2: {}", count_unique_tail_positions(&input, 10));
+    println!("Part 1: {}", count_unique_tail_positions(&input, 2)?);
+    println!("Part 2: {}", count_unique_tail_positions(&input, 10)?);
     Ok(())
 }
 
-fn count_unique_tail_positions(input: &str, size_of_rope: usize) -> usize {
-    let mut rope = VecDeque::from_iter(repeat(Point(0, 0)).take(size_of_rope));
-    let mut visited: HashSet<Point> = HashSet::new();
-    for direction in input.lines().flat_map(parse_moves).flatten() {
-        let head = rope.pop_front().unwrap();
-        let new_head = match direction {
-            Direction::Up => Point(head.0, head.1 + 1),
-            Direction::Down => Point(head.0, head.1 - 1),
-            Direction::Left => Point(head.0 - 1, head.1),
-            Direction::Right => Point(head.0 + 1, head.1),
-        };
-        let next_knot = rope.front().unwrap();
-        let (x_diff, y_diff) = new_head.difference(&next_knot);
-        rope.push_front(new_head);
-        if x_diff.abs() == 2 || y_diff.abs() == 2 {
-            rope = move_rope(rope);
-            visited.insert(*rope.back().unwrap());
-        };
-    }
-    visited.len()
-}
-
-fn move_rope(rope: VecDeque<Point>) -> VecDeque<Point> {
-    let head = rope[0];
-    let mut new_rope = VecDeque::from([head]);
-    let mut previous_knot = head;
-    for knot in rope.into_iter().skip(1) {
-        let (x_diff, y_diff) = previous_knot.difference(&knot);
-        if x_diff.abs() == 2 || y_diff.abs() == 2 {
-            let moved_knot = Point(knot.0 + x_diff.signum(), knot.1 + y_diff.signum());
-            previous_knot = moved_knot;
-            new_rope.push_back(moved_knot);
-        } else {
-            previous_knot = knot;
-            new_rope.push_back(knot);
+// Animate a ten knot rope, emitting the rope and the tail's trail after
+// every motion
+pub fn visualize(vis: &mut dyn Visualize) -> Result<()> {
+    let input = fs::read_to_string(INPUT)?;
+    let mut rope = Rope::new(10)?;
+    for (_, directions) in parse_motions(&input)? {
+        for direction in directions {
+            rope.step(direction);
+        }
+        vis.emit(&rope_frame(rope.knots(), &rope.visited()[9]))?;
+    }
+    Ok(())
+}
+
+// Record every knot move of the ten knot rope, one step per head move
+pub fn trace() -> Result<Trace> {
+    let input = fs::read_to_string(INPUT)?;
+    let mut trace = Trace::new();
+    simulate_rope(&input, 10, &mut trace)?;
+    Ok(trace)
+}
+
+fn count_unique_tail_positions(input: &str, size_of_rope: usize) -> Result<usize> {
+    simulate_rope(input, size_of_rope, &mut NoTrace)
+}
+
+fn simulate_rope(input: &str, size_of_rope: usize, tracer: &mut impl Tracer) -> Result<usize> {
+    let mut rope = Rope::new(size_of_rope)?;
+    let directions = parse_motions(input)?
+        .into_iter()
+        .flat_map(|(_, moves)| moves);
+    for (step, direction) in (0..).zip(directions) {
+        let moved = rope.step(direction);
+        for (knot, point) in (0..).zip(&rope.knots()[..moved]) {
+            tracer.record(Event::new(
+                step,
+                "knot",
+                knot,
+                &[point.0.into(), point.1.into()],
+            ));
         }
     }
-    new_rope
+    Ok(rope.visited()[size_of_rope - 1].len())
 }
 
+// The smallest box around some points, for drawing them with up as the first
+// row. No points at all gives a single cell at the origin.
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    min_x: i16,
+    max_y: i16,
+    width: usize,
+    height: usize,
+}
+
+impl Bounds {
+    fn around<'a>(points: impl Iterator<Item = &'a Point> + Clone) -> Self {
+        let min_x = points.clone().map(|p| p.0).min().unwrap_or(0);
+        let max_x = points.clone().map(|p| p.0).max().unwrap_or(0);
+        let min_y = points.clone().map(|p| p.1).min().unwrap_or(0);
+        let max_y = points.map(|p| p.1).max().unwrap_or(0);
+        Bounds {
+            min_x,
+            max_y,
+            width: (max_x - min_x + 1) as usize,
+            height: (max_y - min_y + 1) as usize,
+        }
+    }
+
+    fn blank(&self) -> Frame {
+        Frame::new(self.width, self.height, '.')
+    }
+
+    fn draw(&self, frame: &mut Frame, point: &Point, cell: char) {
+        frame.set(
+            (point.0 - self.min_x) as usize,
+            (self.max_y - point.1) as usize,
+            cell,
+        )
+    }
+}
+
+// Draw the visited positions as '#' and the knots as 'o' with the head on
+// top, up being the first row.
+fn rope_frame(rope: &[Point], visited: &HashSet<Point>) -> Frame {
+    let bounds = Bounds::around(visited.iter().chain(rope.iter()));
+    let mut frame = bounds.blank();
+    for point in visited {
+        bounds.draw(&mut frame, point, '#');
+    }
+    for point in rope.iter().skip(1) {
+        bounds.draw(&mut frame, point, 'o');
+    }
+    if let Some(head) = rope.first() {
+        bounds.draw(&mut frame, head, 'H');
+    }
+    frame
+}
+
+// A rope after each motion of a run and the positions its tail visited, all
+// drawn in the puzzle's notation inside the box around everywhere any knot
+// went. The start is marked s unless something is on top of it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rendering {
+    pub motions: Vec<(String, Frame)>,
+    pub visited: Frame,
+}
+
+pub fn render_motions(input: &str, length: usize) -> Result<Rendering> {
+    let mut rope = Rope::new(length)?;
+    let mut states = vec![];
+    for (line, directions) in parse_motions(input)? {
+        for direction in directions {
+            rope.step(direction);
+        }
+        states.push((line.to_string(), rope.knots().to_vec()));
+    }
+    let bounds = Bounds::around(rope.visited().iter().flatten());
+    let motions = states
+        .into_iter()
+        .map(|(motion, knots)| {
+            let mut frame = bounds.blank();
+            bounds.draw(&mut frame, &Point(0, 0), 's');
+            for (knot, point) in knots.iter().enumerate().rev() {
+                bounds.draw(&mut frame, point, knot_label(knot, length));
+            }
+            (motion, frame)
+        })
+        .collect();
+    let mut map = bounds.blank();
+    for point in &rope.visited()[length - 1] {
+        bounds.draw(&mut map, point, '#');
+    }
+    bounds.draw(&mut map, &Point(0, 0), 's');
+    Ok(Rendering {
+        motions,
+        visited: map,
+    })
+}
+
+// The head is H and the knots behind it are numbered, except that the tail of
+// a two knot rope is T. Past 9 there are no digits left, so those are all T.
+fn knot_label(knot: usize, length: usize) -> char {
+    match knot {
+        0 => 'H',
+        1 if length == 2 => 'T',
+        1..=9 => char::from_digit(knot as u32, 10).unwrap_or('T'),
+        _ => 'T',
+    }
+}
+
+// Print the rope after every motion of the input, then where its tail went
+pub fn print_motions(length: usize) -> Result<()> {
+    let input = fs::read_to_string(INPUT)?;
+    let rendering = render_motions(&input, length)?;
+    for (motion, frame) in rendering.motions {
+        println!("== {motion} ==\n\n{frame}\n");
+    }
+    println!("== Visited ==\n\n{}", rendering.visited);
+    Ok(())
+}
+
+// Every motion of the input with the line it came from. Blank lines are
+// skipped, but anything else that isn't a motion is an error rather than a
+// motion quietly going missing.
+fn parse_motions(input: &str) -> Result<Vec<(&str, Vec<Direction>)>> {
+    (1..)
+        .zip(input.lines())
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_number, line)| match parse_moves(line) {
+            Some(directions) => Ok((line, directions)),
+            None => Err(format!("Line {line_number}: can't parse '{line}' as a motion.").into()),
+        })
+        .collect()
+}
+
+// "R 4" is four steps right. The diagonals are "UL", "UR", "DL" and "DR".
 fn parse_moves(line: &str) -> Option<Vec<Direction>> {
     let (direction, magnitude) = line.split_once(" ")?;
     let magnitude = magnitude.parse().ok()?;
@@ -79,6 +329,10 @@ fn parse_moves(line: &str) -> Option<Vec<Direction>> {
         "D" => Some(Direction::Down),
         "L" => Some(Direction::Left),
         "R" => Some(Direction::Right),
+        "UL" => Some(Direction::UpLeft),
+        "UR" => Some(Direction::UpRight),
+        "DL" => Some(Direction::DownLeft),
+        "DR" => Some(Direction::DownRight),
         _ => None,
     }?;
     let moves = repeat(direction).take(magnitude);
@@ -91,17 +345,178 @@ mod tests {
     // use test::Bencher;
     use super::*;
 
+    #[test]
+    fn test_rope_frame() -> Result<()> {
+        let rope = [Point(2, 1), Point(1, 0), Point(0, 0)];
+        let visited = HashSet::from([Point(0, 0), Point(-1, 0)]);
+        assert_eq!(rope_frame(&rope, &visited).to_string(), "...H\n#oo.");
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_rope() -> Result<()> {
+        let mut trace = Trace::new();
+        simulate_rope("R 2\nU 1", 2, &mut trace)?;
+        let lines: Vec<String> = trace.events().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "0 knot 0 1 0",
+                "1 knot 0 2 0",
+                "1 knot 1 1 0",
+                "2 knot 0 2 1"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rope() -> Result<()> {
+        assert!(Rope::new(0).is_err());
+        let mut rope = Rope::new(3)?;
+        let mut knots: Vec<Vec<Point>> = vec![];
+        rope.steps(
+            [Direction::Right, Direction::Right, Direction::UpRight],
+            |step, rope| {
+                assert_eq!(step, knots.len());
+                knots.push(rope.to_vec());
+            },
+        );
+        assert_eq!(
+            knots,
+            [
+                vec![Point(1, 0), Point(0, 0), Point(0, 0)],
+                vec![Point(2, 0), Point(1, 0), Point(0, 0)],
+                vec![Point(3, 1), Point(2, 1), Point(1, 1)],
+            ]
+        );
+        assert_eq!((rope.head(), rope.tail()), (Point(3, 1), Point(1, 1)));
+        let mut copy = Rope::new(3)?;
+        let positions: Vec<[Point; 3]> = copy
+            .positions([Direction::Right, Direction::Right, Direction::UpRight])?
+            .collect();
+        assert!(positions
+            .iter()
+            .zip(&knots)
+            .all(|(array, vec)| array[..] == vec[..]));
+        // Cut short once the tail first moves
+        let mut short = Rope::new(2)?;
+        let before_tail_moves = short
+            .positions([Direction::Up; 5])?
+            .take_while(|[_, tail]| *tail == Point(0, 0))
+            .count();
+        assert_eq!(before_tail_moves, 1);
+        assert!(Rope::new(2)?.positions::<3>([]).is_err());
+        assert_eq!(rope.visited()[0].len(), 4);
+        assert_eq!(rope.visited()[1].len(), 3);
+        assert_eq!(rope.visited()[2], HashSet::from([Point(0, 0), Point(1, 1)]));
+        assert_eq!(rope.step(Direction::Left), 1);
+        assert_eq!(rope.step(Direction::DownLeft), 1);
+        assert_eq!(rope.step(Direction::Left), 2);
+
+        let mut single = Rope::new(1)?;
+        assert_eq!(single.step(Direction::Down), 1);
+        assert_eq!(single.tail(), Point(0, -1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagonal_moves() -> Result<()> {
+        assert_eq!(parse_moves("DR 2"), Some(vec![Direction::DownRight; 2]));
+        assert_eq!(parse_moves("X 2"), None);
+        // A diagonal head drags a straight rope diagonally behind it
+        assert_eq!(count_unique_tail_positions("UR 5\nDL 5", 3)?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_motions() -> Result<()> {
+        let input = "R 4\nU 4\nL 3\nD 1\nR 4\nD 1\nL 5\nR 2";
+        let rendering = render_motions(input, 2)?;
+        let (motion, first) = &rendering.motions[0];
+        assert_eq!(motion, "R 4");
+        assert_eq!(first.to_string(), "......\n......\n......\n......\ns..TH.");
+        let (_, last) = &rendering.motions[7];
+        assert_eq!(last.to_string(), "......\n......\n.TH...\n......\ns.....");
+        assert_eq!(
+            rendering.visited,
+            Frame::from_rows(["..##..", "...##.", ".####.", "....#.", "s###.."])
+        );
+
+        let input = "R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20";
+        let rendering = render_motions(input, 10)?;
+        let (_, last) = &rendering.motions[7];
+        let mut expected = vec![".........................."; 21];
+        let knots = ["H", "1", "2", "3", "4", "5", "6", "7", "8", "9"]
+            .map(|knot| format!("{knot}........................."));
+        (0..10).for_each(|row| expected[row] = &knots[row]);
+        expected[15] = "...........s..............";
+        assert_eq!(*last, Frame::from_rows(expected));
+        assert_eq!(
+            rendering.visited.to_string(),
+            [
+                "..........................",
+                "..........................",
+                "..........................",
+                "..........................",
+                "..........................",
+                "..........................",
+                "..........................",
+                "..........................",
+                "..........................",
+                "#.........................",
+                "#.............###.........",
+                "#............#...#........",
+                ".#..........#.....#.......",
+                "..#..........#.....#......",
+                "...#........#.......#.....",
+                "....#......s.........#....",
+                ".....#..............#.....",
+                "......#............#......",
+                ".......#..........#.......",
+                "........#........#........",
+                ".........########.........",
+            ]
+            .join("\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_bad_motion() -> Result<()> {
+        let error = render_motions("R 4\nU four\nL 3", 2).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 2: can't parse 'U four' as a motion."
+        );
+        assert!(render_motions("R 4\nX 1", 2).is_err());
+        assert_eq!(render_motions("R 4\n\nU 1\n", 2)?.motions.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_motion_counts() -> Result<()> {
+        let error = count_unique_tail_positions("R 4\nU four", 2).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Line 2: can't parse 'U four' as a motion."
+        );
+        assert!(count_unique_tail_positions("X 3", 10).is_err());
+        assert_eq!(count_unique_tail_positions("R 4\n\nU 1\n", 2)?, 4);
+        Ok(())
+    }
+
     #[test]
     fn test_part_one() -> Result<()> {
         let input = fs::read_to_string(INPUT)?;
-        assert_eq!(count_unique_tail_positions(&input, 2), 6406);
+        assert_eq!(count_unique_tail_positions(&input, 2)?, 6406);
         Ok(())
     }
 
     #[test]
     fn test_part_two() -> Result<()> {
         let input = fs::read_to_string(INPUT)?;
-        assert_eq!(count_unique_tail_positions(&input, 10), 2643);
+        assert_eq!(count_unique_tail_positions(&input, 10)?, 2643);
         Ok(())
     }
 